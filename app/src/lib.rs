@@ -1,7 +1,55 @@
 use std::env::var;
+use std::fmt;
+use std::str::FromStr;
 
 pub static APPLICATION_NAME: &str = "firetrack";
 
+/// The modes in which the web application can be running.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SiteMode {
+    /// The application is fully operational.
+    Online,
+    /// The application is undergoing maintenance. All requests except health checks are rejected.
+    Maintenance,
+    /// Only requests that do not modify any data are allowed. This is useful while taking backups.
+    ReadOnly,
+}
+
+impl FromStr for SiteMode {
+    type Err = String;
+
+    /// Parses a site mode from its configuration value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use app::SiteMode;
+    ///
+    /// assert_eq!("online".parse(), Ok(SiteMode::Online));
+    /// assert_eq!("maintenance".parse(), Ok(SiteMode::Maintenance));
+    /// assert_eq!("read-only".parse(), Ok(SiteMode::ReadOnly));
+    /// assert!("offline".parse::<SiteMode>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "online" => Ok(SiteMode::Online),
+            "maintenance" => Ok(SiteMode::Maintenance),
+            "read-only" => Ok(SiteMode::ReadOnly),
+            _ => Err(format!("Unknown site mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for SiteMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SiteMode::Online => write!(f, "online"),
+            SiteMode::Maintenance => write!(f, "maintenance"),
+            SiteMode::ReadOnly => write!(f, "read-only"),
+        }
+    }
+}
+
 /// Contains the configuration options for the application. These values are typically coming from
 /// the environment variables and are read only.
 #[derive(Clone, Debug)]
@@ -46,6 +94,17 @@ pub struct AppConfig {
 
     // The port to use for the Mailgun mock server.
     mailgun_mock_server_port: u16,
+
+    // The mode the web application is running in.
+    site_mode: SiteMode,
+
+    // Database operations that take longer than this number of milliseconds are logged. Set to 0 to
+    // disable logging.
+    slow_query_threshold: u64,
+
+    // The bearer token that grants access to the metrics endpoint. The endpoint is disabled if no
+    // token is set.
+    metrics_token: Option<String>,
 }
 
 impl AppConfig {
@@ -74,6 +133,9 @@ impl AppConfig {
     /// # let mailgun_user_domain = "sandbox0123456789abcdef0123456789abcdef.mailgun.org";
     /// # let mailgun_user_name = "postmaster";
     /// # let mailgun_mock_server_port = 8889;
+    /// # let site_mode = app::SiteMode::Online;
+    /// # let slow_query_threshold = 500;
+    /// # let metrics_token = None;
     /// # env::set_var("HOST", host);
     /// # env::set_var("PORT", port.to_string());
     /// # env::set_var("DATABASE_URL", database_url);
@@ -94,6 +156,9 @@ impl AppConfig {
     /// # assert_eq!(config.mailgun_user_domain(), mailgun_user_domain);
     /// # assert_eq!(config.mailgun_user_name(), mailgun_user_name);
     /// # assert_eq!(config.mailgun_mock_server_port(), mailgun_mock_server_port);
+    /// # assert_eq!(config.site_mode(), site_mode);
+    /// # assert_eq!(config.slow_query_threshold(), slow_query_threshold);
+    /// # assert_eq!(config.metrics_token(), metrics_token);
     /// ```
     pub fn from_test_defaults() -> AppConfig {
         import_env_vars();
@@ -122,6 +187,9 @@ impl AppConfig {
                 .expect(
                     "MAILGUN_MOCK_SERVER_PORT environment variable should be an integer value.",
                 ),
+            site_mode: SiteMode::Online,
+            slow_query_threshold: 500,
+            metrics_token: None,
         }
     }
 
@@ -146,6 +214,9 @@ impl AppConfig {
     /// # let mailgun_user_domain = "sandbox0123456789abcdef0123456789abcdef.mailgun.org";
     /// # let mailgun_user_name = "postmaster";
     /// # let mailgun_mock_server_port = 8889;
+    /// # let site_mode = "read-only";
+    /// # let slow_query_threshold = 250;
+    /// # let metrics_token = "my_metrics_token";
     /// # env::set_var("HOST", host);
     /// # env::set_var("PORT", port.to_string());
     /// # env::set_var("SESSION_KEY", session_key.to_string());
@@ -159,6 +230,9 @@ impl AppConfig {
     /// # env::set_var("MAILGUN_USER_DOMAIN", mailgun_user_domain.to_string());
     /// # env::set_var("MAILGUN_USER_NAME", mailgun_user_name.to_string());
     /// # env::set_var("MAILGUN_MOCK_SERVER_PORT", mailgun_mock_server_port.to_string());
+    /// # env::set_var("SITE_MODE", site_mode);
+    /// # env::set_var("SLOW_QUERY_THRESHOLD", slow_query_threshold.to_string());
+    /// # env::set_var("METRICS_TOKEN", metrics_token);
     ///
     /// let config = AppConfig::from_environment();
     ///
@@ -175,6 +249,9 @@ impl AppConfig {
     /// # assert_eq!(config.mailgun_user_domain(), mailgun_user_domain);
     /// # assert_eq!(config.mailgun_user_name(), mailgun_user_name);
     /// # assert_eq!(config.mailgun_mock_server_port(), mailgun_mock_server_port);
+    /// # assert_eq!(config.site_mode(), app::SiteMode::ReadOnly);
+    /// # assert_eq!(config.slow_query_threshold(), slow_query_threshold);
+    /// # assert_eq!(config.metrics_token(), Some(metrics_token));
     /// ```
     pub fn from_environment() -> AppConfig {
         import_env_vars();
@@ -231,6 +308,24 @@ impl AppConfig {
                 .expect(
                     "MAILGUN_MOCK_SERVER_PORT environment variable should be an integer value.",
                 ),
+            // The site mode was introduced after the initial release. Default to online so that
+            // existing deployments keep working.
+            site_mode: match var("SITE_MODE") {
+                Ok(site_mode) => site_mode.parse().expect(
+                    "SITE_MODE environment variable should be 'online', 'maintenance' or 'read-only'.",
+                ),
+                Err(_) => SiteMode::Online,
+            },
+            // Log operations that take longer than half a second if no threshold is configured.
+            slow_query_threshold: match var("SLOW_QUERY_THRESHOLD") {
+                Ok(threshold) => threshold.parse().expect(
+                    "SLOW_QUERY_THRESHOLD environment variable should be an integer value.",
+                ),
+                Err(_) => 500,
+            },
+            // An empty token is treated as not set, so the metrics are never exposed without
+            // authentication.
+            metrics_token: var("METRICS_TOKEN").ok().filter(|token| !token.is_empty()),
         }
     }
 
@@ -424,6 +519,49 @@ impl AppConfig {
         self.mailgun_mock_server_port
     }
 
+    /// Returns the mode the web application is running in.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use app::{AppConfig, SiteMode};
+    ///
+    /// let config = AppConfig::from_test_defaults();
+    /// assert_eq!(config.site_mode(), SiteMode::Online);
+    /// ```
+    pub fn site_mode(&self) -> SiteMode {
+        self.site_mode
+    }
+
+    /// Returns the number of milliseconds after which a database operation is logged as being slow.
+    /// A value of 0 means slow operations are not logged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use app::AppConfig;
+    ///
+    /// let config = AppConfig::from_test_defaults();
+    /// assert_eq!(config.slow_query_threshold(), 500);
+    /// ```
+    pub fn slow_query_threshold(&self) -> u64 {
+        self.slow_query_threshold
+    }
+
+    /// Returns the bearer token that grants access to the metrics endpoint, if one is set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use app::AppConfig;
+    ///
+    /// let config = AppConfig::from_test_defaults();
+    /// assert_eq!(config.metrics_token(), None);
+    /// ```
+    pub fn metrics_token(&self) -> Option<&str> {
+        self.metrics_token.as_deref()
+    }
+
     // Todo: this should only be used for testing. Adding #[cfg(test)] doesn't work if the test code
     // is in another crate, because the method will not be found. Define a newtype in the test?
     pub fn set_default_categories_json_path(&mut self, default_categories_json_path: String) {
@@ -434,6 +572,16 @@ impl AppConfig {
     pub fn set_mailgun_api_key(&mut self, mailgun_api_key: String) {
         self.mailgun_api_key = mailgun_api_key;
     }
+
+    // Todo: this should only be used for testing.
+    pub fn set_site_mode(&mut self, site_mode: SiteMode) {
+        self.site_mode = site_mode;
+    }
+
+    // Todo: this should only be used for testing.
+    pub fn set_metrics_token(&mut self, metrics_token: Option<String>) {
+        self.metrics_token = metrics_token;
+    }
 }
 
 /// Configures log output levels as defined in the `RUST_LOG` environment variable.
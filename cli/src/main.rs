@@ -4,13 +4,16 @@ extern crate clap;
 extern crate log;
 
 use app::*;
+use chrono::Datelike;
 use clap::{AppSettings, Arg, SubCommand};
 use db::establish_connection;
+use diesel::Connection;
 use rust_decimal::Decimal;
 use serde_json::json;
 use std::env;
 use std::process::exit;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 use web::serve;
 
 /// A trait that defines functions that will log an error and exit with an error code.
@@ -93,7 +96,12 @@ async fn main() {
             .bin_name(APPLICATION_NAME)
             .subcommand(
                 SubCommand::with_name("serve")
-                    .about(format!("Serve the {} web application", APPLICATION_NAME).as_str()),
+                    .about(format!("Serve the {} web application", APPLICATION_NAME).as_str())
+                    .arg(
+                        Arg::with_name("skip-check")
+                            .long("skip-check")
+                            .help("Start the web server even if the database schema version does not match the expected version"),
+                    ),
             )
             .subcommand(
                 SubCommand::with_name("user")
@@ -192,6 +200,23 @@ async fn main() {
                             .arg(Arg::with_name("email").required(true).help(
                                 "The email address of the account for which to populate the categories",
                             )),
+                        SubCommand::with_name("templates")
+                            .about("Lists the bundled category templates"),
+                        SubCommand::with_name("import-template")
+                            .about("Imports a category template, adding the categories that do not exist yet")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account for which to import the categories",
+                            ))
+                            .arg(
+                                Arg::with_name("template")
+                                    .required(true)
+                                    .help("The name of a bundled template, or the path to a JSON file"),
+                            ),
+                        SubCommand::with_name("export-template")
+                            .about("Outputs the categories of an account as a template in JSON format")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account for which to export the categories",
+                            )),
                     ])
                     .setting(AppSettings::SubcommandRequiredElseHelp),
             )
@@ -226,6 +251,18 @@ async fn main() {
                                     .long("date")
                                     .takes_value(true)
                                     .help("The date for the expense, in the format YYYY-MM-DD. If omitted, today's date will be used."),
+                            )
+                            .arg(
+                                Arg::with_name("project_id")
+                                    .long("project")
+                                    .takes_value(true)
+                                    .help("The ID of the project or trip the expense is part of"),
+                            )
+                            .arg(
+                                Arg::with_name("payer")
+                                    .long("reimbursable-by")
+                                    .takes_value(true)
+                                    .help("The person or organization that will pay back the expense"),
                             ),
                         SubCommand::with_name("get")
                             .about("Outputs an expense as JSON data")
@@ -236,6 +273,212 @@ async fn main() {
                     ])
                     .setting(AppSettings::SubcommandRequiredElseHelp),
             )
+            .subcommand(
+                SubCommand::with_name("merchant")
+                    .about("Commands for managing merchant names")
+                    .subcommands(vec![
+                        SubCommand::with_name("add-alias")
+                            .about("Maps descriptions containing a pattern to a merchant name")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account for which to create the alias",
+                            ))
+                            .arg(
+                                Arg::with_name("pattern")
+                                    .required(true)
+                                    .help("The text to look for in the description"),
+                            )
+                            .arg(
+                                Arg::with_name("name")
+                                    .required(true)
+                                    .help("The merchant name"),
+                            ),
+                        SubCommand::with_name("delete-alias")
+                            .about("Deletes a merchant alias")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account for which to delete the alias",
+                            ))
+                            .arg(
+                                Arg::with_name("pattern")
+                                    .required(true)
+                                    .help("The pattern of the alias to delete"),
+                            ),
+                        SubCommand::with_name("aliases")
+                            .about("Outputs the merchant aliases as JSON data")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account for which to output the aliases",
+                            )),
+                        SubCommand::with_name("normalize")
+                            .about("Outputs the merchant name for a description")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account whose aliases to use",
+                            ))
+                            .arg(
+                                Arg::with_name("description")
+                                    .required(true)
+                                    .help("The description as it appears on the bank statement"),
+                            ),
+                    ])
+                    .setting(AppSettings::SubcommandRequiredElseHelp),
+            )
+            .subcommand(
+                SubCommand::with_name("project")
+                    .about("Commands for managing projects and trips")
+                    .subcommands(vec![
+                        SubCommand::with_name("add")
+                            .about("Create a new project")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account for which to create the project",
+                            ))
+                            .arg(
+                                Arg::with_name("name")
+                                    .required(true)
+                                    .help("The project name"),
+                            )
+                            .arg(
+                                Arg::with_name("budget")
+                                    .long("budget")
+                                    .short("b")
+                                    .takes_value(true)
+                                    .help("The amount that can be spent on the project"),
+                            ),
+                        SubCommand::with_name("get")
+                            .about("Outputs a project with its totals per category as JSON data")
+                            .arg(Arg::with_name("id").required(true).help("The project ID")),
+                        SubCommand::with_name("delete")
+                            .about("Deletes a project. The expenses in the project are kept.")
+                            .arg(Arg::with_name("id").required(true).help("The project ID")),
+                    ])
+                    .setting(AppSettings::SubcommandRequiredElseHelp),
+            )
+            .subcommand(
+                SubCommand::with_name("reimbursement")
+                    .about("Commands for managing expenses that will be paid back")
+                    .subcommands(vec![
+                        SubCommand::with_name("mark")
+                            .about("Marks an expense as reimbursable")
+                            .arg(Arg::with_name("id").required(true).help("The expense ID"))
+                            .arg(
+                                Arg::with_name("payer")
+                                    .required(true)
+                                    .help("The person or organization that will pay back the expense"),
+                            ),
+                        SubCommand::with_name("unmark")
+                            .about("Marks an expense as not reimbursable")
+                            .arg(Arg::with_name("id").required(true).help("The expense ID")),
+                        SubCommand::with_name("outstanding")
+                            .about("Outputs the expenses that have not been paid back per payer as JSON data")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account for which to output the expenses",
+                            )),
+                        SubCommand::with_name("receive")
+                            .about("Records a payment and marks the matching expenses as reimbursed")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account that received the payment",
+                            ))
+                            .arg(
+                                Arg::with_name("payer")
+                                    .required(true)
+                                    .help("The person or organization that made the payment"),
+                            )
+                            .arg(
+                                Arg::with_name("amount")
+                                    .required(true)
+                                    .help("The amount that was received"),
+                            )
+                            .arg(
+                                Arg::with_name("date")
+                                    .long("date")
+                                    .takes_value(true)
+                                    .help("The date of the payment, in the format YYYY-MM-DD. If omitted, today's date will be used."),
+                            ),
+                    ])
+                    .setting(AppSettings::SubcommandRequiredElseHelp),
+            )
+            .subcommand(
+                SubCommand::with_name("tax")
+                    .about("Commands for managing the taxes paid on expenses")
+                    .subcommands(vec![
+                        SubCommand::with_name("set")
+                            .about("Sets the tax information of an expense")
+                            .arg(Arg::with_name("id").required(true).help("The expense ID"))
+                            .arg(
+                                Arg::with_name("rate")
+                                    .required(true)
+                                    .help("The tax rate as a percentage, e.g. 21.00"),
+                            )
+                            .arg(
+                                Arg::with_name("amount")
+                                    .long("amount")
+                                    .short("a")
+                                    .takes_value(true)
+                                    .help("The tax amount. If omitted, it is calculated from the tax rate."),
+                            )
+                            .arg(
+                                Arg::with_name("deductible")
+                                    .long("deductible")
+                                    .help("Whether the tax can be deducted"),
+                            ),
+                        SubCommand::with_name("remove")
+                            .about("Removes the tax information from an expense")
+                            .arg(Arg::with_name("id").required(true).help("The expense ID")),
+                        SubCommand::with_name("set-default")
+                            .about("Sets the tax information that is applied to new expenses in a category")
+                            .arg(Arg::with_name("category_id").required(true).help("The category ID"))
+                            .arg(
+                                Arg::with_name("rate")
+                                    .required(true)
+                                    .help("The tax rate as a percentage, e.g. 21.00"),
+                            )
+                            .arg(
+                                Arg::with_name("deductible")
+                                    .long("deductible")
+                                    .help("Whether the tax can be deducted"),
+                            ),
+                        SubCommand::with_name("remove-default")
+                            .about("Removes the tax information that is applied to new expenses in a category")
+                            .arg(Arg::with_name("category_id").required(true).help("The category ID")),
+                    ])
+                    .setting(AppSettings::SubcommandRequiredElseHelp),
+            )
+            .subcommand(
+                SubCommand::with_name("report")
+                    .about("Commands for reporting on expenses")
+                    .subcommands(vec![
+                        SubCommand::with_name("monthly")
+                            .about("Outputs the totals per category for a month as JSON data")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account for which to output the totals",
+                            ))
+                            .arg(
+                                Arg::with_name("month")
+                                    .long("month")
+                                    .takes_value(true)
+                                    .help("The month, in the format YYYY-MM. If omitted, the current month will be used."),
+                            ),
+                        SubCommand::with_name("tax")
+                            .about("Outputs the taxes paid on expenses during a year")
+                            .arg(Arg::with_name("email").required(true).help(
+                                "The email address of the account for which to output the taxes",
+                            ))
+                            .arg(
+                                Arg::with_name("year")
+                                    .long("year")
+                                    .takes_value(true)
+                                    .help("The year, in the format YYYY. If omitted, the current year will be used."),
+                            )
+                            .arg(
+                                Arg::with_name("format")
+                                    .long("format")
+                                    .takes_value(true)
+                                    .possible_values(&["csv", "json"])
+                                    .default_value("csv")
+                                    .help("The output format"),
+                            ),
+                        SubCommand::with_name("refresh")
+                            .about("Rebuilds the monthly totals from the expenses"),
+                    ])
+                    .setting(AppSettings::SubcommandRequiredElseHelp),
+            )
             .subcommand(
                 SubCommand::with_name("notify")
                     .about("Send a notification")
@@ -257,8 +500,22 @@ async fn main() {
             .get_matches();
 
     // Launch the passed in subcommand.
+    let slow_command_threshold = Duration::from_millis(config.slow_query_threshold());
+    let start = Instant::now();
     match cli_app.subcommand() {
-        ("serve", _) => {
+        ("serve", Some(arguments)) => {
+            // Refuse to serve the application on a database with a different schema version, since
+            // this might corrupt the data.
+            let connection = establish_connection(config.database_url()).unwrap_or_exit();
+            if let Err(e) = db::check_schema_version(&connection) {
+                if arguments.is_present("skip-check") {
+                    warn!("{}", e);
+                } else {
+                    error!("{}", e);
+                    error!("Run the database migrations or deploy the matching version of the application. Pass --skip-check to start the web server anyway.");
+                    exit(1);
+                }
+            }
             serve(config).await.unwrap_or_exit();
         }
         ("user", Some(arguments)) => match arguments.subcommand() {
@@ -368,6 +625,35 @@ async fn main() {
                 )
                 .unwrap_or_exit();
             }
+            ("templates", _) => {
+                for template in db::category_template::TEMPLATES {
+                    println!("{}: {}", template.name, template.description);
+                }
+            }
+            ("import-template", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+
+                // Use the bundled template with the given name, or otherwise read the given file.
+                let template = arguments.value_of("template").unwrap();
+                let categories = match db::category_template::get_template(template) {
+                    Some(template) => template.categories(),
+                    None => db::category_template::read_file(std::path::Path::new(template)),
+                }
+                .unwrap_or_exit();
+
+                let count =
+                    db::category_template::import(&connection, &user, &categories).unwrap_or_exit();
+                println!("Created {} categories", count);
+            }
+            ("export-template", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+                let categories = db::category_template::export(&connection, &user).unwrap_or_exit();
+                println!("{}", categories);
+            }
             ("", None) => {}
             _ => unreachable!(),
         },
@@ -408,15 +694,43 @@ async fn main() {
                     Err::<String, _>(message).unwrap_or_exit();
                 };
 
-                db::expense::create(
-                    &establish_connection(&config.database_url()).unwrap_or_exit(),
-                    &user,
-                    &amount,
-                    &category.unwrap(),
-                    arguments.value_of("description"),
-                    date.as_ref(),
-                )
-                .unwrap_or_exit();
+                // Load the project, if one was passed. It should belong to the same user.
+                let project_id =
+                    assert_integer_argument(arguments.value_of("project_id"), "project ID");
+                let project = project_id.map(|id| {
+                    db::project::read(&connection, id)
+                        .filter(|p| p.user_id == user.id)
+                        .ok_or_else(|| format!("Project with ID {} could not be loaded", id))
+                        .unwrap_or_exit()
+                });
+
+                // Add the expense to the project and mark it as reimbursable in the same
+                // transaction, so the expense is not created if this fails.
+                connection
+                    .transaction::<_, Box<dyn std::error::Error>, _>(|| {
+                        let expense = db::expense::create(
+                            &connection,
+                            &user,
+                            &amount,
+                            &category.unwrap(),
+                            arguments.value_of("description"),
+                            date.as_ref(),
+                        )
+                        .map_err(|e| e.to_string())?;
+
+                        if let Some(project) = project {
+                            db::project::add_expense(&connection, &project, &expense)
+                                .map_err(|e| e.to_string())?;
+                        }
+
+                        if let Some(payer) = arguments.value_of("payer") {
+                            db::reimbursement::mark_reimbursable(&connection, &expense, payer)
+                                .map_err(|e| e.to_string())?;
+                        }
+
+                        Ok(())
+                    })
+                    .unwrap_or_exit();
             }
             ("get", Some(arguments)) => {
                 let id = assert_integer_argument(arguments.value_of("id"), "expense ID").unwrap();
@@ -435,6 +749,240 @@ async fn main() {
             ("", None) => {}
             _ => unreachable!(),
         },
+        ("merchant", Some(arguments)) => match arguments.subcommand() {
+            ("add-alias", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+                db::merchant::create_alias(
+                    &connection,
+                    &user,
+                    arguments.value_of("pattern").unwrap(),
+                    arguments.value_of("name").unwrap(),
+                )
+                .unwrap_or_exit();
+            }
+            ("delete-alias", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+                db::merchant::delete_alias(
+                    &connection,
+                    &user,
+                    arguments.value_of("pattern").unwrap(),
+                )
+                .unwrap_or_exit();
+            }
+            ("aliases", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+                let aliases = db::merchant::get_aliases(&connection, &user).unwrap_or_exit();
+                println!("{}", json!(aliases));
+            }
+            ("normalize", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+                let normalizer =
+                    db::merchant::Normalizer::load(&connection, &user).unwrap_or_exit();
+                println!(
+                    "{}",
+                    normalizer.normalize(arguments.value_of("description").unwrap())
+                );
+            }
+            ("", None) => {}
+            _ => unreachable!(),
+        },
+        ("project", Some(arguments)) => match arguments.subcommand() {
+            ("add", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+                let budget = arguments
+                    .value_of("budget")
+                    .map(|b| Decimal::from_str(b).unwrap_or_exit());
+                db::project::create(
+                    &connection,
+                    &user,
+                    arguments.value_of("name").unwrap(),
+                    budget.as_ref(),
+                )
+                .unwrap_or_exit();
+            }
+            ("get", Some(arguments)) => {
+                let id = assert_integer_argument(arguments.value_of("id"), "project ID").unwrap();
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let project = db::project::read(&connection, id)
+                    .ok_or("Project not found")
+                    .unwrap_or_exit();
+                let summary = db::project::get_summary(&connection, &project).unwrap_or_exit();
+                println!("{}", json!(summary));
+            }
+            ("delete", Some(arguments)) => {
+                let id = assert_integer_argument(arguments.value_of("id"), "project ID").unwrap();
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                db::project::delete(&connection, id).unwrap_or_exit();
+            }
+            ("", None) => {}
+            _ => unreachable!(),
+        },
+        ("reimbursement", Some(arguments)) => match arguments.subcommand() {
+            ("mark", Some(arguments)) => {
+                let id = assert_integer_argument(arguments.value_of("id"), "expense ID").unwrap();
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let expense = db::expense::read(&connection, id)
+                    .ok_or("Expense not found")
+                    .unwrap_or_exit();
+                db::reimbursement::mark_reimbursable(
+                    &connection,
+                    &expense,
+                    arguments.value_of("payer").unwrap(),
+                )
+                .unwrap_or_exit();
+            }
+            ("unmark", Some(arguments)) => {
+                let id = assert_integer_argument(arguments.value_of("id"), "expense ID").unwrap();
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let expense = db::expense::read(&connection, id)
+                    .ok_or("Expense not found")
+                    .unwrap_or_exit();
+                db::reimbursement::unmark_reimbursable(&connection, &expense).unwrap_or_exit();
+            }
+            ("outstanding", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+                let outstanding =
+                    db::reimbursement::get_outstanding(&connection, &user).unwrap_or_exit();
+                println!("{}", json!(outstanding));
+            }
+            ("receive", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+                let amount = Decimal::from_str(arguments.value_of("amount").unwrap())
+                    .map_err(|_| "Amount should be in the format \"149.99\"".to_string())
+                    .unwrap_or_exit();
+                let date = match arguments.value_of("date") {
+                    Some(d) => chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d")
+                        .map_err(|_| {
+                            "The date should be valid and in the format YYYY-MM-DD".to_string()
+                        })
+                        .unwrap_or_exit(),
+                    None => chrono::Local::today().naive_local(),
+                };
+                let reimbursed = db::reimbursement::record_payment(
+                    &connection,
+                    &user,
+                    arguments.value_of("payer").unwrap(),
+                    &amount,
+                    &date,
+                )
+                .unwrap_or_exit();
+                println!("{}", json!(reimbursed));
+            }
+            ("", None) => {}
+            _ => unreachable!(),
+        },
+        ("tax", Some(arguments)) => match arguments.subcommand() {
+            ("set", Some(arguments)) => {
+                let id = assert_integer_argument(arguments.value_of("id"), "expense ID").unwrap();
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let expense = db::expense::read(&connection, id)
+                    .ok_or("Expense not found")
+                    .unwrap_or_exit();
+                let rate = assert_decimal_argument(arguments.value_of("rate"), "tax rate").unwrap();
+                let amount = assert_decimal_argument(arguments.value_of("amount"), "tax amount");
+                let tax = db::tax::set_expense_tax(
+                    &connection,
+                    &expense,
+                    &rate,
+                    amount.as_ref(),
+                    arguments.is_present("deductible"),
+                )
+                .unwrap_or_exit();
+                println!("{}", json!(tax));
+            }
+            ("remove", Some(arguments)) => {
+                let id = assert_integer_argument(arguments.value_of("id"), "expense ID").unwrap();
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let expense = db::expense::read(&connection, id)
+                    .ok_or("Expense not found")
+                    .unwrap_or_exit();
+                db::tax::remove_expense_tax(&connection, &expense).unwrap_or_exit();
+            }
+            ("set-default", Some(arguments)) => {
+                let id = assert_integer_argument(arguments.value_of("category_id"), "category ID")
+                    .unwrap();
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let category = db::category::read(&connection, id)
+                    .ok_or("Category not found")
+                    .unwrap_or_exit();
+                let rate = assert_decimal_argument(arguments.value_of("rate"), "tax rate").unwrap();
+                db::tax::set_category_default(
+                    &connection,
+                    &category,
+                    &rate,
+                    arguments.is_present("deductible"),
+                )
+                .unwrap_or_exit();
+            }
+            ("remove-default", Some(arguments)) => {
+                let id = assert_integer_argument(arguments.value_of("category_id"), "category ID")
+                    .unwrap();
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let category = db::category::read(&connection, id)
+                    .ok_or("Category not found")
+                    .unwrap_or_exit();
+                db::tax::remove_category_default(&connection, &category).unwrap_or_exit();
+            }
+            ("", None) => {}
+            _ => unreachable!(),
+        },
+        ("report", Some(arguments)) => match arguments.subcommand() {
+            ("monthly", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+
+                let month = match arguments.value_of("month") {
+                    Some(m) => chrono::NaiveDate::parse_from_str(&format!("{}-01", m), "%Y-%m-%d")
+                        .map_err(|_| "The month should be in the format YYYY-MM".to_string())
+                        .unwrap_or_exit(),
+                    None => chrono::Local::today().naive_local(),
+                };
+
+                let totals =
+                    db::monthly_total::get_totals(&connection, &user, &month).unwrap_or_exit();
+                println!("{}", json!(totals));
+            }
+            ("tax", Some(arguments)) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                let email = arguments.value_of("email").unwrap();
+                let user = db::user::read(&connection, email).unwrap_or_exit();
+
+                let year = match arguments.value_of("year") {
+                    Some(y) => y
+                        .parse()
+                        .map_err(|_| "The year should be in the format YYYY".to_string())
+                        .unwrap_or_exit(),
+                    None => chrono::Local::today().year(),
+                };
+
+                let report = db::tax::get_yearly_report(&connection, &user, year).unwrap_or_exit();
+                match arguments.value_of("format") {
+                    Some("json") => println!("{}", json!(report)),
+                    _ => print!("{}", report.to_csv()),
+                }
+            }
+            ("refresh", _) => {
+                let connection = establish_connection(&config.database_url()).unwrap_or_exit();
+                db::monthly_total::refresh(&connection).unwrap_or_exit();
+            }
+            ("", None) => {}
+            _ => unreachable!(),
+        },
         ("notify", Some(notify)) => match notify.subcommand() {
             ("activate", Some(arguments)) => {
                 let connection = establish_connection(&config.database_url()).unwrap_or_exit();
@@ -455,9 +1003,33 @@ async fn main() {
         _ => unreachable!(),
     }
 
+    // Log commands that exceed the slow query threshold. The time includes work that is not done
+    // by the database, like hashing passwords, so these are not counted as slow database
+    // operations. The servers run until they are stopped and sending notifications mostly waits on
+    // Mailgun, so these are not timed.
+    let duration = start.elapsed();
+    if let (command, Some(arguments)) = cli_app.subcommand() {
+        if slow_command_threshold > Duration::from_millis(0)
+            && duration >= slow_command_threshold
+            && !["serve", "notify", "mailgun-mock-server"].contains(&command)
+        {
+            let mut context = format!("{} {}", APPLICATION_NAME, command);
+            if let Some(subcommand) = arguments.subcommand_name() {
+                context = format!("{} {}", context, subcommand);
+            }
+            warn!("Slow command {} took {} ms", context, duration.as_millis());
+        }
+    }
+
     // Checks that the given argument can be casted to an integer.
     fn assert_integer_argument(arg: Option<&str>, arg_type: &str) -> Option<i32> {
         let msg = format!("The {} must be an integer", arg_type);
         arg.map(|v| v.parse().map_err(|_| msg).unwrap_or_exit())
     }
+
+    // Checks that the given argument is a decimal number.
+    fn assert_decimal_argument(arg: Option<&str>, arg_type: &str) -> Option<Decimal> {
+        let msg = format!("The {} should be in the format \"21.00\"", arg_type);
+        arg.map(|v| Decimal::from_str(v).map_err(|_| msg).unwrap_or_exit())
+    }
 }
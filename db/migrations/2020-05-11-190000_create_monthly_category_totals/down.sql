@@ -0,0 +1 @@
+DROP TABLE monthly_category_totals;
@@ -0,0 +1,15 @@
+CREATE TABLE monthly_category_totals (
+  category_id INTEGER NOT NULL REFERENCES categories (id) ON DELETE CASCADE,
+  month DATE NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  total NUMERIC(15, 2) NOT NULL,
+  expense_count INTEGER NOT NULL,
+  PRIMARY KEY (category_id, month)
+);
+
+CREATE INDEX monthly_category_totals_user_month_index ON monthly_category_totals (user_id, month);
+
+INSERT INTO monthly_category_totals (category_id, month, user_id, total, expense_count)
+  SELECT category_id, DATE_TRUNC('month', date)::DATE, user_id, SUM(amount), COUNT(*)
+  FROM expenses
+  GROUP BY category_id, DATE_TRUNC('month', date), user_id;
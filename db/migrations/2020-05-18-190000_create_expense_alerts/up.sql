@@ -0,0 +1,10 @@
+CREATE TABLE expense_alerts (
+  id SERIAL PRIMARY KEY,
+  expense_id INTEGER NOT NULL REFERENCES expenses (id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  reason VARCHAR(50) NOT NULL,
+  status VARCHAR(50) NOT NULL DEFAULT 'pending',
+  created TIMESTAMP NOT NULL
+);
+
+CREATE INDEX expense_alerts_user_status_index ON expense_alerts (user_id, status);
@@ -0,0 +1,2 @@
+DROP TABLE recurring_expense_occurrences;
+DROP TABLE recurring_expenses;
@@ -0,0 +1,19 @@
+CREATE TABLE recurring_expenses (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  category_id INTEGER NOT NULL REFERENCES categories (id) ON DELETE CASCADE,
+  description VARCHAR(255) NOT NULL,
+  amount NUMERIC(9, 2) NOT NULL,
+  interval_days INTEGER NOT NULL,
+  next_date DATE NOT NULL
+);
+
+CREATE INDEX recurring_expenses_user_index ON recurring_expenses (user_id);
+
+-- Links expenses to the recurring expense they are an occurrence of.
+CREATE TABLE recurring_expense_occurrences (
+  expense_id INTEGER PRIMARY KEY REFERENCES expenses (id) ON DELETE CASCADE,
+  recurring_expense_id INTEGER NOT NULL REFERENCES recurring_expenses (id) ON DELETE CASCADE
+);
+
+CREATE INDEX recurring_expense_occurrences_recurring_expense_index ON recurring_expense_occurrences (recurring_expense_id);
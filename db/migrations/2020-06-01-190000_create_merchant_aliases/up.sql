@@ -0,0 +1,7 @@
+CREATE TABLE merchant_aliases (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  pattern VARCHAR(255) NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  UNIQUE (user_id, pattern)
+);
@@ -0,0 +1,15 @@
+CREATE TABLE projects (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  name VARCHAR(255) NOT NULL,
+  budget NUMERIC(9, 2),
+  UNIQUE (user_id, name)
+);
+
+-- Links expenses to the project they are part of. An expense can be part of at most one project.
+CREATE TABLE project_expenses (
+  expense_id INTEGER PRIMARY KEY REFERENCES expenses (id) ON DELETE CASCADE,
+  project_id INTEGER NOT NULL REFERENCES projects (id) ON DELETE CASCADE
+);
+
+CREATE INDEX project_expenses_project_index ON project_expenses (project_id);
@@ -0,0 +1,10 @@
+-- Expenses that are paid personally but will be paid back by someone else, e.g. an employer.
+CREATE TABLE reimbursements (
+  expense_id INTEGER PRIMARY KEY REFERENCES expenses (id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  payer VARCHAR(255) NOT NULL,
+  status VARCHAR(50) NOT NULL DEFAULT 'outstanding',
+  reimbursed_date DATE
+);
+
+CREATE INDEX reimbursements_user_status_index ON reimbursements (user_id, status);
@@ -0,0 +1,2 @@
+DROP TABLE category_tax_defaults;
+DROP TABLE expense_taxes;
@@ -0,0 +1,15 @@
+-- Tax information of expenses. The tax rate is a percentage and the tax amount is included in the
+-- amount of the expense.
+CREATE TABLE expense_taxes (
+  expense_id INTEGER PRIMARY KEY REFERENCES expenses (id) ON DELETE CASCADE,
+  tax_rate NUMERIC(5, 2) NOT NULL,
+  tax_amount NUMERIC(9, 2) NOT NULL,
+  deductible BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+-- The tax information that is applied to new expenses in a category.
+CREATE TABLE category_tax_defaults (
+  category_id INTEGER PRIMARY KEY REFERENCES categories (id) ON DELETE CASCADE,
+  tax_rate NUMERIC(5, 2) NOT NULL,
+  deductible BOOLEAN NOT NULL DEFAULT FALSE
+);
@@ -0,0 +1,5 @@
+-- A reference that identifies an imported expense in its source, e.g. the transaction ID on a bank
+-- statement. Expenses with a reference that was already imported are skipped, so an import can be
+-- run again.
+ALTER TABLE expenses ADD COLUMN import_reference VARCHAR;
+CREATE UNIQUE INDEX expenses_user_id_import_reference_key ON expenses (user_id, import_reference);
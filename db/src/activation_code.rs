@@ -1,6 +1,7 @@
 use super::schema::activation_codes;
 use super::schema::activation_codes::dsl;
 use super::user::{User, UserErrorKind};
+use super::{AsyncError, ConnectionPool};
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use rand::{thread_rng, Rng};
@@ -335,6 +336,37 @@ fn assert_not_activated(user: &User) -> Result<(), ActivationCodeErrorKind> {
     Ok(())
 }
 
+/// Returns an activation code for the given user without blocking the async executor.
+pub async fn get_async(
+    pool: &ConnectionPool,
+    user: &User,
+) -> Result<ActivationCode, AsyncError<ActivationCodeErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| get(connection, &user)).await
+}
+
+/// Activates the given user if the given activation code is valid, without blocking the async
+/// executor.
+pub async fn activate_user_async(
+    pool: &ConnectionPool,
+    user: User,
+    activation_code: i32,
+) -> Result<User, AsyncError<ActivationCodeErrorKind>> {
+    super::run(pool, move |connection| {
+        activate_user(connection, user, activation_code)
+    })
+    .await
+}
+
+/// Deletes the activation code for the given user without blocking the async executor.
+pub async fn delete_async(
+    pool: &ConnectionPool,
+    user: &User,
+) -> Result<(), AsyncError<ActivationCodeErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| delete(connection, &user)).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,6 +1,7 @@
 use super::schema::categories;
 use super::schema::categories::dsl;
 use super::user::User;
+use super::{AsyncError, ConnectionPool};
 use app::AppConfig;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
@@ -297,6 +298,53 @@ fn insert_child_categories(
     Ok(result)
 }
 
+/// Creates a category without blocking the async executor.
+pub async fn create_async(
+    pool: &ConnectionPool,
+    user: &User,
+    name: &str,
+    description: Option<&str>,
+    parent: Option<&Category>,
+) -> Result<Category, AsyncError<CategoryErrorKind>> {
+    let (user, name) = (user.clone(), name.to_string());
+    let (description, parent) = (description.map(str::to_string), parent.cloned());
+    super::run(pool, move |connection| {
+        create(
+            connection,
+            &user,
+            &name,
+            description.as_deref(),
+            parent.as_ref(),
+        )
+    })
+    .await
+}
+
+/// Retrieves the category with the given ID without blocking the async executor.
+pub async fn read_async(
+    pool: &ConnectionPool,
+    id: i32,
+) -> Result<Option<Category>, AsyncError<CategoryErrorKind>> {
+    super::run(pool, move |connection| Ok(read(connection, id))).await
+}
+
+/// Deletes the category with the given ID without blocking the async executor.
+pub async fn delete_async(
+    pool: &ConnectionPool,
+    id: i32,
+) -> Result<(), AsyncError<CategoryErrorKind>> {
+    super::run(pool, move |connection| delete(connection, id)).await
+}
+
+/// Returns the given user's categories without blocking the async executor.
+pub async fn get_categories_async(
+    pool: &ConnectionPool,
+    user: &User,
+) -> Result<Vec<Category>, AsyncError<CategoryErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| get_categories(connection, &user)).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
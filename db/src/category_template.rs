@@ -0,0 +1,259 @@
+use super::category::{self, Category, CategoryErrorKind};
+use super::user::User;
+use super::{AsyncError, ConnectionPool};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{fs::File, path::Path};
+
+/// A bundled set of categories that can be imported into the category tree of a user.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CategoryTemplate {
+    // The name that is used to refer to the template, e.g. on the command line.
+    pub name: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    // The categories in the same JSON format as the default categories.
+    #[serde(skip)]
+    json: &'static str,
+}
+
+impl CategoryTemplate {
+    /// Returns the categories of the template as JSON data.
+    pub fn categories(&self) -> Result<Value, CategoryErrorKind> {
+        serde_json::from_str(self.json).map_err(|_| CategoryErrorKind::MalformedCategoryList)
+    }
+}
+
+/// The category templates that are bundled with the application.
+pub const TEMPLATES: &[CategoryTemplate] = &[
+    CategoryTemplate {
+        name: "standard-household",
+        title: "Standard household",
+        description: "Everyday expenses like housing, food, transportation and utilities.",
+        json: include_str!("../../resources/category-templates/standard-household.json"),
+    },
+    CategoryTemplate {
+        name: "freelancer",
+        title: "Freelancer",
+        description: "Business expenses like equipment, software, travel and taxes.",
+        json: include_str!("../../resources/category-templates/freelancer.json"),
+    },
+];
+
+/// Returns the bundled template with the given name.
+pub fn get_template(name: &str) -> Option<&'static CategoryTemplate> {
+    TEMPLATES.iter().find(|t| t.name == name)
+}
+
+/// Reads a category template from the JSON file at the given path, e.g. one that was created with
+/// `export()`.
+pub fn read_file(path: &Path) -> Result<Value, CategoryErrorKind> {
+    let file = File::open(path).map_err(|e| {
+        CategoryErrorKind::IoError(path.to_string_lossy().to_string(), e.to_string())
+    })?;
+    serde_json::from_reader(file).map_err(|_| CategoryErrorKind::MalformedCategoryList)
+}
+
+/// Merges the categories in the given JSON data into the category tree of the given user. The data
+/// has the same format as the default categories. Categories that already exist in the same parent
+/// category are reused rather than created again, ignoring differences in case and surrounding
+/// whitespace. Returns the number of categories that were created.
+pub fn import(
+    connection: &PgConnection,
+    user: &User,
+    json: &Value,
+) -> Result<usize, CategoryErrorKind> {
+    connection.transaction::<_, CategoryErrorKind, _>(|| {
+        let mut existing = category::get_categories(connection, user)?;
+        merge(connection, user, json, None, &mut existing)
+    })
+}
+
+/// Merges the categories in the given JSON data into the category tree of the given user without
+/// blocking the async executor.
+pub async fn import_async(
+    pool: &ConnectionPool,
+    user: &User,
+    json: Value,
+) -> Result<usize, AsyncError<CategoryErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| import(connection, &user, &json)).await
+}
+
+/// Exports the category tree of the given user as JSON data that can be imported as a template.
+pub fn export(connection: &PgConnection, user: &User) -> Result<Value, CategoryErrorKind> {
+    let categories = category::get_categories(connection, user)?;
+    Ok(export_children(&categories, None))
+}
+
+// Creates the categories in the given JSON data that do not exist yet inside the given parent
+// category, and recurses into their children. The created categories are added to `existing`.
+fn merge(
+    connection: &PgConnection,
+    user: &User,
+    json: &Value,
+    parent: Option<&Category>,
+    existing: &mut Vec<Category>,
+) -> Result<usize, CategoryErrorKind> {
+    // Collect the category names together with their children, if any.
+    let entries: Vec<(&str, Option<&Value>)> = match json {
+        Value::Object(o) => o.iter().map(|(k, v)| (k.as_str(), Some(v))).collect(),
+        Value::Array(a) => a
+            .iter()
+            .map(|c| c.as_str().map(|name| (name, None)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(CategoryErrorKind::MalformedCategoryList)?,
+        _ => return Err(CategoryErrorKind::MalformedCategoryList),
+    };
+
+    let parent_id = parent.map(|p| p.id);
+    let mut created = 0;
+    for (name, children) in entries {
+        let name = name.trim();
+        let found = existing
+            .iter()
+            .find(|c| c.parent_id == parent_id && c.name.to_lowercase() == name.to_lowercase())
+            .cloned();
+        let category = match found {
+            Some(category) => category,
+            None => {
+                let category = category::create(connection, user, name, None, parent)?;
+                existing.push(category.clone());
+                created += 1;
+                category
+            }
+        };
+        if let Some(children) = children {
+            created += merge(connection, user, children, Some(&category), existing)?;
+        }
+    }
+
+    Ok(created)
+}
+
+// Returns the children of the given parent category as JSON data. If none of the children have
+// children of their own they are returned as an array of names, otherwise as an object.
+fn export_children(categories: &[Category], parent_id: Option<i32>) -> Value {
+    let children: Vec<&Category> = categories
+        .iter()
+        .filter(|c| c.parent_id == parent_id)
+        .collect();
+
+    if children
+        .iter()
+        .all(|child| !categories.iter().any(|c| c.parent_id == Some(child.id)))
+    {
+        let mut names: Vec<&str> = children.iter().map(|c| c.name.as_str()).collect();
+        names.sort_unstable();
+        return Value::from(names);
+    }
+
+    let mut map = Map::new();
+    for child in children {
+        map.insert(
+            child.name.clone(),
+            export_children(categories, Some(child.id)),
+        );
+    }
+    Value::Object(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_test::*;
+    use crate::{establish_connection, get_database_url};
+    use app::AppConfig;
+    use diesel::result::Error;
+    use serde_json::json;
+
+    // Tests that the bundled templates contain valid data.
+    #[test]
+    fn test_templates() {
+        for template in TEMPLATES {
+            assert_eq!(Some(template), get_template(template.name));
+            assert!(template.categories().unwrap().is_object());
+        }
+        assert_eq!(None, get_template("non-existing"));
+    }
+
+    // Tests that importing a template merges it into the existing categories.
+    #[test]
+    fn test_import() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let food = category::create(&conn, &user, "Food", None, None).unwrap();
+            category::create(&conn, &user, "Groceries", None, Some(&food)).unwrap();
+
+            let template = json!({
+                "food ": ["Groceries", "Restaurants"],
+                "Utilities": {"Energy": ["Electricity", "Gas"], "Water": []},
+            });
+            assert_eq!(6, import(&conn, &user, &template).unwrap());
+
+            let expected = json!({
+                "Food": ["Groceries", "Restaurants"],
+                "Utilities": {"Energy": ["Electricity", "Gas"], "Water": []},
+            });
+            assert_eq!(expected, export(&conn, &user).unwrap());
+
+            // Importing the same template again does not create any categories.
+            assert_eq!(0, import(&conn, &user, &template).unwrap());
+            assert_eq!(expected, export(&conn, &user).unwrap());
+
+            // The categories of other users are not affected.
+            let other_user = create_test_user(&conn, &config);
+            assert_eq!(json!([]), export(&conn, &other_user).unwrap());
+            assert_eq!(8, import(&conn, &other_user, &template).unwrap());
+
+            Ok(())
+        });
+    }
+
+    // Tests that nothing is imported if the template contains malformed data.
+    #[test]
+    fn test_import_malformed() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+
+            for template in &[json!("Food"), json!({"Food": ["Groceries", 1]})] {
+                assert_eq!(
+                    CategoryErrorKind::MalformedCategoryList,
+                    import(&conn, &user, template).unwrap_err()
+                );
+            }
+            assert_eq!(json!([]), export(&conn, &user).unwrap());
+
+            Ok(())
+        });
+    }
+
+    // Tests importing a template from a file.
+    #[test]
+    fn test_read_file() {
+        let path = Path::new("../resources/fixtures/default-categories.json");
+        let expected = json!({
+            "Food": ["Alcohol", "Groceries"],
+            "Utilities": ["Electricity", "Internet", "Water"],
+        });
+        assert_eq!(expected, read_file(path).unwrap());
+
+        let path = Path::new("../resources/fixtures/malformed-default-categories2.json");
+        assert_eq!(
+            CategoryErrorKind::MalformedCategoryList,
+            read_file(path).unwrap_err()
+        );
+        assert!(matches!(
+            read_file(Path::new("non-existing.json")),
+            Err(CategoryErrorKind::IoError(_, _))
+        ));
+    }
+}
@@ -1,14 +1,30 @@
 use super::category::Category;
+use super::expense_alert;
+use super::monthly_total;
+use super::recurring;
 use super::schema::expenses;
 use super::schema::expenses::dsl;
+use super::tax;
 use super::user::User;
+use super::{AsyncError, ConnectionPool};
 use chrono::Utc;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use rust_decimal::Decimal;
 use serde::Serialize;
+use std::collections::HashSet;
 use std::fmt;
 
+// The maximum number of expenses that are inserted in a single statement by `create_many()`. Every
+// expense takes 6 bind parameters and PostgreSQL allows at most 65535 parameters per statement.
+#[cfg(not(test))]
+pub(crate) const BULK_INSERT_CHUNK_SIZE: usize = 10_000;
+
+// Tests use small chunks so inserting more than one chunk is covered without creating thousands of
+// expenses.
+#[cfg(test)]
+pub(crate) const BULK_INSERT_CHUNK_SIZE: usize = 10;
+
 #[derive(Associations, Clone, Debug, PartialEq, Queryable, Serialize)]
 #[belongs_to(Category, foreign_key = "id")]
 #[belongs_to(User, foreign_key = "id")]
@@ -19,6 +35,30 @@ pub struct Expense {
     pub category_id: i32,
     pub user_id: i32,
     pub date: chrono::NaiveDate,
+    // The reference of the expense in the data it was imported from, if any.
+    pub import_reference: Option<String>,
+}
+
+/// An expense to create using `create_many()`.
+#[derive(Clone, Debug)]
+pub struct NewExpense<'a> {
+    pub amount: Decimal,
+    pub category: &'a Category,
+    pub description: Option<&'a str>,
+    pub date: Option<chrono::NaiveDate>,
+    /// A reference that identifies the expense in the imported data, e.g. the transaction ID on a
+    /// bank statement. An expense with a reference that was already imported is skipped.
+    pub import_reference: Option<&'a str>,
+}
+
+/// The result of `create_many()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreateManyResult {
+    /// The expenses that were created, ordered by ID.
+    pub created: Vec<Expense>,
+    /// The import references of the expenses that were skipped because they were already imported,
+    /// in the order they were passed.
+    pub skipped: Vec<String>,
 }
 
 // Possible errors thrown when handling expenses.
@@ -54,7 +94,10 @@ impl fmt::Display for ExpenseErrorKind {
     }
 }
 
-/// Creates an expense.
+/// Creates an expense. If the category has tax defaults these are applied to the expense. If the
+/// expense is an occurrence of a recurring expense it is linked to it. If the expense looks
+/// unusual, e.g. because it might be a duplicate, an expense alert is created for the user to
+/// review.
 pub fn create(
     connection: &PgConnection,
     user: &User,
@@ -63,6 +106,119 @@ pub fn create(
     description: Option<&str>,
     date: Option<&chrono::NaiveDate>,
 ) -> Result<Expense, ExpenseErrorKind> {
+    validate(user, amount, category)?;
+
+    connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            let expense = diesel::insert_into(dsl::expenses)
+                .values((
+                    dsl::amount.eq(amount),
+                    dsl::description.eq(description),
+                    dsl::category_id.eq(category.id),
+                    dsl::user_id.eq(user.id),
+                    dsl::date.eq(date.unwrap_or(&Utc::now().naive_utc().date())),
+                ))
+                .returning((
+                    dsl::id,
+                    dsl::amount,
+                    dsl::description,
+                    dsl::category_id,
+                    dsl::user_id,
+                    dsl::date,
+                    dsl::import_reference,
+                ))
+                .get_result(connection)?;
+            monthly_total::add_expenses(connection, std::slice::from_ref(&expense))?;
+            tax::apply_category_defaults(connection, std::slice::from_ref(&expense))?;
+            recurring::link_occurrences(connection, user, std::slice::from_ref(&expense))?;
+            expense_alert::check(connection, user, &expense)?;
+            Ok(expense)
+        })
+        .map_err(ExpenseErrorKind::CreationFailed)
+}
+
+/// Creates multiple expenses for the given user. This uses multi-row inserts which is a lot faster
+/// than calling `create()` for every expense when importing large numbers of expenses.
+///
+/// All expenses are validated before any of them are inserted. The expenses are inserted in a single
+/// transaction, so if one of them cannot be created none of them are. Expenses with an import
+/// reference that was already imported, either earlier or further up in the same list, are skipped
+/// and reported in the result, so an import that was interrupted can be run again. Unlike
+/// `create()` this does not check for unusual expenses, since bulk imports typically contain a lot
+/// of similar expenses.
+pub fn create_many(
+    connection: &PgConnection,
+    user: &User,
+    expenses: &[NewExpense],
+) -> Result<CreateManyResult, ExpenseErrorKind> {
+    for expense in expenses {
+        validate(user, &expense.amount, expense.category)?;
+    }
+
+    let today = Utc::now().naive_utc().date();
+    connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            let mut created = Vec::with_capacity(expenses.len());
+            for chunk in expenses.chunks(BULK_INSERT_CHUNK_SIZE) {
+                let values: Vec<_> = chunk
+                    .iter()
+                    .map(|expense| {
+                        (
+                            dsl::amount.eq(expense.amount),
+                            dsl::description.eq(expense.description),
+                            dsl::category_id.eq(expense.category.id),
+                            dsl::user_id.eq(user.id),
+                            dsl::date.eq(expense.date.unwrap_or(today)),
+                            dsl::import_reference.eq(expense.import_reference),
+                        )
+                    })
+                    .collect();
+                // Expenses that conflict with the unique import reference of the user are not
+                // inserted and are not returned.
+                let mut result = diesel::insert_into(dsl::expenses)
+                    .values(values)
+                    .on_conflict_do_nothing()
+                    .returning((
+                        dsl::id,
+                        dsl::amount,
+                        dsl::description,
+                        dsl::category_id,
+                        dsl::user_id,
+                        dsl::date,
+                        dsl::import_reference,
+                    ))
+                    .get_results::<Expense>(connection)?;
+                created.append(&mut result);
+            }
+            // PostgreSQL does not guarantee the order of the returned rows.
+            created.sort_unstable_by_key(|e| e.id);
+            monthly_total::add_expenses(connection, &created)?;
+            tax::apply_category_defaults(connection, &created)?;
+            recurring::link_occurrences(connection, user, &created)?;
+
+            let skipped = get_skipped_references(expenses, &created);
+            Ok(CreateManyResult { created, skipped })
+        })
+        .map_err(ExpenseErrorKind::CreationFailed)
+}
+
+// Returns the import references of the given new expenses that were not created. If a reference
+// occurs more than once only the first occurrence can have been created.
+fn get_skipped_references(expenses: &[NewExpense], created: &[Expense]) -> Vec<String> {
+    let mut created_references: HashSet<&str> = created
+        .iter()
+        .filter_map(|e| e.import_reference.as_deref())
+        .collect();
+    expenses
+        .iter()
+        .filter_map(|e| e.import_reference)
+        .filter(|reference| !created_references.remove(reference))
+        .map(str::to_string)
+        .collect()
+}
+
+// Checks that the given amount and category are valid for an expense of the given user.
+fn validate(user: &User, amount: &Decimal, category: &Category) -> Result<(), ExpenseErrorKind> {
     // Check that the category belongs to the same user.
     if category.user_id != user.id {
         return Err(ExpenseErrorKind::CategoryHasWrongUser);
@@ -72,24 +228,7 @@ pub fn create(
         return Err(ExpenseErrorKind::InvalidAmount);
     }
 
-    diesel::insert_into(dsl::expenses)
-        .values((
-            dsl::amount.eq(amount),
-            dsl::description.eq(description),
-            dsl::category_id.eq(category.id),
-            dsl::user_id.eq(user.id),
-            dsl::date.eq(date.unwrap_or(&Utc::now().naive_utc().date())),
-        ))
-        .returning((
-            dsl::id,
-            dsl::amount,
-            dsl::description,
-            dsl::category_id,
-            dsl::user_id,
-            dsl::date,
-        ))
-        .get_result(connection)
-        .map_err(ExpenseErrorKind::CreationFailed)
+    Ok(())
 }
 
 /// Retrieves the expense with the given ID.
@@ -104,18 +243,72 @@ pub fn read(connection: &PgConnection, id: i32) -> Option<Expense> {
 
 /// Deletes the expense with the given ID.
 pub fn delete(connection: &PgConnection, id: i32) -> Result<(), ExpenseErrorKind> {
-    let result = diesel::delete(dsl::expenses.filter(dsl::id.eq(id))).execute(connection);
+    let result = connection.transaction::<_, diesel::result::Error, _>(|| {
+        let deleted = diesel::delete(dsl::expenses.filter(dsl::id.eq(id)))
+            .returning((
+                dsl::id,
+                dsl::amount,
+                dsl::description,
+                dsl::category_id,
+                dsl::user_id,
+                dsl::date,
+                dsl::import_reference,
+            ))
+            .get_results::<Expense>(connection)?;
+        monthly_total::remove_expenses(connection, &deleted)?;
+        Ok(deleted)
+    });
 
     let result = result.map_err(ExpenseErrorKind::DeletionFailed)?;
 
     // Throw an error if nothing was deleted.
-    if result == 0 {
+    if result.is_empty() {
         return Err(ExpenseErrorKind::NotFound(id));
     }
 
     Ok(())
 }
 
+/// Creates an expense without blocking the async executor.
+pub async fn create_async(
+    pool: &ConnectionPool,
+    user: &User,
+    amount: &Decimal,
+    category: &Category,
+    description: Option<&str>,
+    date: Option<&chrono::NaiveDate>,
+) -> Result<Expense, AsyncError<ExpenseErrorKind>> {
+    let (user, amount, category) = (user.clone(), *amount, category.clone());
+    let (description, date) = (description.map(str::to_string), date.cloned());
+    super::run(pool, move |connection| {
+        create(
+            connection,
+            &user,
+            &amount,
+            &category,
+            description.as_deref(),
+            date.as_ref(),
+        )
+    })
+    .await
+}
+
+/// Retrieves the expense with the given ID without blocking the async executor.
+pub async fn read_async(
+    pool: &ConnectionPool,
+    id: i32,
+) -> Result<Option<Expense>, AsyncError<ExpenseErrorKind>> {
+    super::run(pool, move |connection| Ok(read(connection, id))).await
+}
+
+/// Deletes the expense with the given ID without blocking the async executor.
+pub async fn delete_async(
+    pool: &ConnectionPool,
+    id: i32,
+) -> Result<(), AsyncError<ExpenseErrorKind>> {
+    super::run(pool, move |connection| delete(connection, id)).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -255,6 +448,145 @@ mod tests {
         });
     }
 
+    // Tests super::create_many().
+    #[test]
+    fn test_create_many() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat1 = create_test_category(&conn, &user);
+            let cat2 = create_test_category(&conn, &user);
+            let date = chrono::NaiveDate::from_ymd(2020, 5, 1);
+
+            // Create enough expenses to span multiple chunks.
+            let count = BULK_INSERT_CHUNK_SIZE * 2 + 5;
+            let new_expenses: Vec<NewExpense> = (1..=count as i64)
+                .map(|i| NewExpense {
+                    amount: Decimal::new(i, 2),
+                    category: if i % 2 == 0 { &cat1 } else { &cat2 },
+                    description: if i % 3 == 0 { Some("Coffee") } else { None },
+                    date: if i % 5 == 0 { None } else { Some(date) },
+                    import_reference: None,
+                })
+                .collect();
+
+            // All expenses should be created and returned ordered by ID. The amounts are unique so
+            // they can be used to find the expense that was created for each new expense.
+            let result = create_many(&conn, &user, &new_expenses).unwrap();
+            let expenses = result.created;
+            assert!(result.skipped.is_empty());
+            assert_eq!(count, expenses.len());
+            assert_expense_count(&conn, count as i64);
+            assert!(expenses.windows(2).all(|pair| pair[0].id < pair[1].id));
+            for new_expense in &new_expenses {
+                let expense = expenses
+                    .iter()
+                    .find(|e| e.amount == new_expense.amount)
+                    .unwrap();
+                assert_expense(
+                    expense,
+                    None,
+                    &new_expense.amount,
+                    new_expense.description,
+                    new_expense.category.id,
+                    user.id,
+                    new_expense.date.unwrap_or(Utc::now().naive_utc().date()),
+                );
+                assert_eq!(None, expense.import_reference);
+            }
+
+            // If one of the expenses is invalid, none of them should be created.
+            let other_user = create_test_user(&conn, &config);
+            let other_user_cat = create_test_category(&conn, &other_user);
+            let mut invalid_expenses = new_expenses.clone();
+            invalid_expenses[BULK_INSERT_CHUNK_SIZE + 1].category = &other_user_cat;
+            let result = create_many(&conn, &user, &invalid_expenses);
+            assert_eq!(ExpenseErrorKind::CategoryHasWrongUser, result.unwrap_err());
+
+            let mut invalid_expenses = new_expenses.clone();
+            invalid_expenses[count - 1].amount = Decimal::new(0, 2);
+            let result = create_many(&conn, &user, &invalid_expenses);
+            assert_eq!(ExpenseErrorKind::InvalidAmount, result.unwrap_err());
+
+            assert_expense_count(&conn, count as i64);
+
+            Ok(())
+        });
+    }
+
+    // Tests that super::create_many() skips expenses that were already imported.
+    #[test]
+    fn test_create_many_with_import_references() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = create_test_category(&conn, &user);
+            let references: Vec<String> = (0..BULK_INSERT_CHUNK_SIZE * 2)
+                .map(|i| format!("transaction-{}", i))
+                .collect();
+            fn new_expense<'a>(
+                category: &'a Category,
+                reference: Option<&'a str>,
+            ) -> NewExpense<'a> {
+                NewExpense {
+                    amount: Decimal::new(1000, 2),
+                    category,
+                    description: None,
+                    date: None,
+                    import_reference: reference,
+                }
+            }
+
+            // Import the first half of the expenses, as if the import was interrupted.
+            let first_half: Vec<NewExpense> = references[..BULK_INSERT_CHUNK_SIZE]
+                .iter()
+                .map(|r| new_expense(&cat, Some(r)))
+                .collect();
+            let result = create_many(&conn, &user, &first_half).unwrap();
+            assert_eq!(BULK_INSERT_CHUNK_SIZE, result.created.len());
+            assert!(result.skipped.is_empty());
+
+            // Running the full import again only creates the missing expenses. A reference that
+            // occurs twice in the imported data is only created once, also when the duplicate is
+            // in a later chunk. Expenses without a reference are always created.
+            let mut all: Vec<NewExpense> = references
+                .iter()
+                .map(|r| new_expense(&cat, Some(r)))
+                .collect();
+            all.push(new_expense(&cat, Some(&references[BULK_INSERT_CHUNK_SIZE])));
+            all.push(new_expense(&cat, None));
+            let result = create_many(&conn, &user, &all).unwrap();
+            let created_references: Vec<Option<&str>> = result
+                .created
+                .iter()
+                .map(|e| e.import_reference.as_deref())
+                .collect();
+            assert_eq!(BULK_INSERT_CHUNK_SIZE + 1, created_references.len());
+            for reference in &references[BULK_INSERT_CHUNK_SIZE..] {
+                assert!(created_references.contains(&Some(reference.as_str())));
+            }
+            assert!(created_references.contains(&None));
+            let mut expected_skipped = references[..BULK_INSERT_CHUNK_SIZE].to_vec();
+            expected_skipped.push(references[BULK_INSERT_CHUNK_SIZE].clone());
+            assert_eq!(expected_skipped, result.skipped);
+            assert_expense_count(&conn, (BULK_INSERT_CHUNK_SIZE * 2 + 1) as i64);
+
+            // The same references can be used by other users.
+            let other_user = create_test_user(&conn, &config);
+            let other_cat = create_test_category(&conn, &other_user);
+            let other_expenses = vec![new_expense(&other_cat, Some(&references[0]))];
+            let result = create_many(&conn, &other_user, &other_expenses).unwrap();
+            assert_eq!(1, result.created.len());
+            assert!(result.skipped.is_empty());
+
+            Ok(())
+        });
+    }
+
     // Tests super::read().
     #[test]
     fn test_read() {
@@ -0,0 +1,596 @@
+use super::expense::Expense;
+use super::merchant::Normalizer;
+use super::schema::expense_alerts::dsl;
+use super::schema::expenses;
+use super::user::User;
+use super::{AsyncError, ConnectionPool};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Date, Integer, Nullable, Numeric};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fmt;
+
+// The minimum number of earlier expenses at a payee or in a category that is needed to tell whether
+// an amount is unusual.
+const MIN_HISTORY_SIZE: i64 = 5;
+
+// Amounts that exceed the average of the earlier expenses by more than this number of standard
+// deviations are considered to be unusual.
+const MAX_STANDARD_DEVIATIONS: i64 = 3;
+
+/// An alert about an expense that looks unusual, e.g. a duplicate charge or a price hike of a
+/// subscription. The user can confirm the expense is correct or dismiss the alert.
+#[derive(Clone, Debug, PartialEq, Queryable, Serialize)]
+pub struct ExpenseAlert {
+    pub id: i32,
+    pub expense_id: i32,
+    pub user_id: i32,
+    pub reason: String,
+    pub status: String,
+    pub created: chrono::NaiveDateTime,
+}
+
+/// The reasons for which an expense can be flagged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlertReason {
+    /// An expense with the same amount and date already exists at the same payee, or in the same
+    /// category if the payee is not known.
+    Duplicate,
+    /// The amount is a lot higher than what is usually spent at the payee, or in the category if
+    /// there is not enough history for the payee.
+    UnusualAmount,
+}
+
+impl AlertReason {
+    /// Returns the value that is stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertReason::Duplicate => "duplicate",
+            AlertReason::UnusualAmount => "unusual_amount",
+        }
+    }
+}
+
+/// The statuses of an alert.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlertStatus {
+    /// The alert has not been reviewed yet.
+    Pending,
+    /// The user has confirmed that the expense is correct.
+    Confirmed,
+    /// The user has dismissed the alert.
+    Dismissed,
+}
+
+impl AlertStatus {
+    /// Returns the value that is stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertStatus::Pending => "pending",
+            AlertStatus::Confirmed => "confirmed",
+            AlertStatus::Dismissed => "dismissed",
+        }
+    }
+}
+
+// Possible errors thrown when handling expense alerts.
+#[derive(Debug, PartialEq)]
+pub enum ExpenseAlertErrorKind {
+    // The alert with the given ID does not exist or belongs to a different user.
+    NotFound(i32),
+    // The alerts could not be read due to a database error.
+    ReadFailed(diesel::result::Error),
+    // An alert could not be updated due to a database error.
+    UpdateFailed(diesel::result::Error),
+}
+
+impl fmt::Display for ExpenseAlertErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExpenseAlertErrorKind::NotFound(ref id) => write!(f, "Expense alert {} not found", id),
+            ExpenseAlertErrorKind::ReadFailed(ref err) => {
+                write!(f, "Database error when reading expense alerts: {}", err)
+            }
+            ExpenseAlertErrorKind::UpdateFailed(ref err) => {
+                write!(f, "Database error when updating expense alert: {}", err)
+            }
+        }
+    }
+}
+
+/// Returns the alerts of the given user that have not been reviewed yet, together with the
+/// expenses they are about. The most recent alerts are returned first.
+pub fn get_pending(
+    connection: &PgConnection,
+    user: &User,
+) -> Result<Vec<(ExpenseAlert, Expense)>, ExpenseAlertErrorKind> {
+    dsl::expense_alerts
+        .inner_join(expenses::table)
+        .filter(dsl::user_id.eq(user.id))
+        .filter(dsl::status.eq(AlertStatus::Pending.as_str()))
+        .order(dsl::created.desc())
+        .load::<(ExpenseAlert, Expense)>(connection)
+        .map_err(ExpenseAlertErrorKind::ReadFailed)
+}
+
+/// Returns the number of alerts of the given user that have not been reviewed yet.
+pub fn count_pending(connection: &PgConnection, user: &User) -> Result<i64, ExpenseAlertErrorKind> {
+    dsl::expense_alerts
+        .filter(dsl::user_id.eq(user.id))
+        .filter(dsl::status.eq(AlertStatus::Pending.as_str()))
+        .count()
+        .get_result(connection)
+        .map_err(ExpenseAlertErrorKind::ReadFailed)
+}
+
+/// Sets the status of the alert with the given ID, e.g. when the user confirms or dismisses it.
+pub fn resolve(
+    connection: &PgConnection,
+    user: &User,
+    id: i32,
+    status: AlertStatus,
+) -> Result<ExpenseAlert, ExpenseAlertErrorKind> {
+    let result = diesel::update(
+        dsl::expense_alerts
+            .filter(dsl::id.eq(id))
+            .filter(dsl::user_id.eq(user.id)),
+    )
+    .set(dsl::status.eq(status.as_str()))
+    .get_result::<ExpenseAlert>(connection);
+
+    match result {
+        Ok(alert) => Ok(alert),
+        Err(diesel::result::Error::NotFound) => Err(ExpenseAlertErrorKind::NotFound(id)),
+        Err(e) => Err(ExpenseAlertErrorKind::UpdateFailed(e)),
+    }
+}
+
+/// Returns the pending alerts of the given user without blocking the async executor.
+pub async fn get_pending_async(
+    pool: &ConnectionPool,
+    user: &User,
+) -> Result<Vec<(ExpenseAlert, Expense)>, AsyncError<ExpenseAlertErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| get_pending(connection, &user)).await
+}
+
+/// Returns the number of pending alerts of the given user without blocking the async executor.
+pub async fn count_pending_async(
+    pool: &ConnectionPool,
+    user: &User,
+) -> Result<i64, AsyncError<ExpenseAlertErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| count_pending(connection, &user)).await
+}
+
+/// Sets the status of the alert with the given ID without blocking the async executor.
+pub async fn resolve_async(
+    pool: &ConnectionPool,
+    user: &User,
+    id: i32,
+    status: AlertStatus,
+) -> Result<ExpenseAlert, AsyncError<ExpenseAlertErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| {
+        resolve(connection, &user, id, status)
+    })
+    .await
+}
+
+// Checks whether the given newly created expense looks unusual and creates an alert if it does.
+pub(crate) fn check(
+    connection: &PgConnection,
+    user: &User,
+    expense: &Expense,
+) -> QueryResult<Option<ExpenseAlert>> {
+    let normalizer = Normalizer::load(connection, user)?;
+    let reason = if is_duplicate(connection, expense, &normalizer)? {
+        AlertReason::Duplicate
+    } else if is_unusual_amount(connection, expense, &normalizer)? {
+        AlertReason::UnusualAmount
+    } else {
+        return Ok(None);
+    };
+
+    diesel::insert_into(dsl::expense_alerts)
+        .values((
+            dsl::expense_id.eq(expense.id),
+            dsl::user_id.eq(expense.user_id),
+            dsl::reason.eq(reason.as_str()),
+            dsl::status.eq(AlertStatus::Pending.as_str()),
+            dsl::created.eq(chrono::Local::now().naive_local()),
+        ))
+        .get_result(connection)
+        .map(Some)
+}
+
+// Returns whether another expense of the user with the same amount and date exists at the same
+// payee. If either expense has no description the payee is not known, and they are compared by
+// category instead.
+fn is_duplicate(
+    connection: &PgConnection,
+    expense: &Expense,
+    normalizer: &Normalizer,
+) -> QueryResult<bool> {
+    let payee = get_payee(expense, normalizer);
+    Ok(expenses::table
+        .filter(expenses::user_id.eq(expense.user_id))
+        .filter(expenses::id.ne(expense.id))
+        .filter(expenses::amount.eq(expense.amount))
+        .filter(expenses::date.eq(expense.date))
+        .select((expenses::category_id, expenses::description))
+        .load::<(i32, Option<String>)>(connection)?
+        .iter()
+        .any(|(category_id, description)| match (&payee, description) {
+            (Some(payee), Some(description)) => *payee == normalizer.normalize(description),
+            _ => *category_id == expense.category_id,
+        }))
+}
+
+// Statistics about the amounts of a set of expenses.
+#[derive(QueryableByName)]
+struct Statistics {
+    #[sql_type = "BigInt"]
+    count: i64,
+    #[sql_type = "Nullable<Numeric>"]
+    average: Option<Decimal>,
+    #[sql_type = "Nullable<Numeric>"]
+    standard_deviation: Option<Decimal>,
+}
+
+// Returns whether the amount of the given expense is a lot higher than the amounts of the earlier
+// expenses at the same payee. If there are not enough of these, the earlier expenses in the same
+// category are used instead. Expenses on the same day count as earlier expenses.
+fn is_unusual_amount(
+    connection: &PgConnection,
+    expense: &Expense,
+    normalizer: &Normalizer,
+) -> QueryResult<bool> {
+    let payee_history = get_payee_history(connection, expense, normalizer)?;
+    let statistics = if payee_history.len() as i64 >= MIN_HISTORY_SIZE {
+        diesel::sql_query(
+            "SELECT COUNT(*) AS count, AVG(amount) AS average, STDDEV_SAMP(amount) AS standard_deviation
+            FROM expenses
+            WHERE id = ANY($1)",
+        )
+        .bind::<Array<Integer>, _>(payee_history)
+        .get_result::<Statistics>(connection)?
+    } else {
+        diesel::sql_query(
+            "SELECT COUNT(*) AS count, AVG(amount) AS average, STDDEV_SAMP(amount) AS standard_deviation
+            FROM expenses
+            WHERE category_id = $1 AND id <> $2 AND date <= $3",
+        )
+        .bind::<Integer, _>(expense.category_id)
+        .bind::<Integer, _>(expense.id)
+        .bind::<Date, _>(expense.date)
+        .get_result::<Statistics>(connection)?
+    };
+
+    if statistics.count < MIN_HISTORY_SIZE {
+        return Ok(false);
+    }
+
+    match (statistics.average, statistics.standard_deviation) {
+        (Some(average), Some(standard_deviation)) => {
+            Ok(expense.amount
+                > average + standard_deviation * Decimal::from(MAX_STANDARD_DEVIATIONS))
+        }
+        _ => Ok(false),
+    }
+}
+
+// Returns the IDs of the earlier expenses of the user at the same payee as the given expense.
+fn get_payee_history(
+    connection: &PgConnection,
+    expense: &Expense,
+    normalizer: &Normalizer,
+) -> QueryResult<Vec<i32>> {
+    let payee = match get_payee(expense, normalizer) {
+        Some(payee) => payee,
+        None => return Ok(vec![]),
+    };
+
+    Ok(expenses::table
+        .filter(expenses::user_id.eq(expense.user_id))
+        .filter(expenses::id.ne(expense.id))
+        .filter(expenses::date.le(expense.date))
+        .filter(expenses::description.is_not_null())
+        .select((expenses::id, expenses::description))
+        .load::<(i32, Option<String>)>(connection)?
+        .into_iter()
+        .filter_map(|(id, description)| {
+            description
+                .filter(|d| normalizer.normalize(d) == payee)
+                .map(|_| id)
+        })
+        .collect())
+}
+
+// Returns the payee of the given expense, which is the merchant name in its description.
+fn get_payee(expense: &Expense, normalizer: &Normalizer) -> Option<String> {
+    expense
+        .description
+        .as_deref()
+        .filter(|description| !description.trim().is_empty())
+        .map(|description| normalizer.normalize(description))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::category::Category;
+    use crate::db_test::*;
+    use crate::{establish_connection, get_database_url};
+    use app::AppConfig;
+    use diesel::result::Error;
+    use std::str::FromStr;
+
+    // Tests that duplicate expenses are flagged.
+    #[test]
+    fn test_duplicate() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat1 = create_test_category(&conn, &user);
+            let cat2 = create_test_category(&conn, &user);
+
+            create_expense(&conn, &user, &cat1, "12.99", "2020-05-01");
+            assert!(get_pending(&conn, &user).unwrap().is_empty());
+
+            // Expenses with the same amount on other days or in other categories are fine.
+            create_expense(&conn, &user, &cat1, "12.99", "2020-05-02");
+            create_expense(&conn, &user, &cat2, "12.99", "2020-05-01");
+            assert!(get_pending(&conn, &user).unwrap().is_empty());
+
+            let duplicate = create_expense(&conn, &user, &cat1, "12.99", "2020-05-01");
+            let alerts = get_pending(&conn, &user).unwrap();
+            assert_eq!(1, alerts.len());
+            assert_eq!(duplicate, alerts[0].1);
+            assert_eq!(AlertReason::Duplicate.as_str(), alerts[0].0.reason);
+            assert_eq!(AlertStatus::Pending.as_str(), alerts[0].0.status);
+
+            Ok(())
+        });
+    }
+
+    // Tests that expenses with a description are compared by payee to find duplicates.
+    #[test]
+    fn test_duplicate_payee() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat1 = create_test_category(&conn, &user);
+            let cat2 = create_test_category(&conn, &user);
+
+            create_payee_expense(
+                &conn,
+                &user,
+                &cat1,
+                "CARD 1234 BAKERY SMITH",
+                "4.50",
+                "2020-05-01",
+            );
+
+            // Purchases with the same amount at other payees in the same category are fine.
+            create_payee_expense(
+                &conn,
+                &user,
+                &cat1,
+                "CARD 1234 BUTCHER JONES",
+                "4.50",
+                "2020-05-01",
+            );
+            assert!(get_pending(&conn, &user).unwrap().is_empty());
+
+            // A charge at the same payee is a duplicate, even if it is in another category.
+            let duplicate =
+                create_payee_expense(&conn, &user, &cat2, "Bakery Smith", "4.50", "2020-05-01");
+            let alerts = get_pending(&conn, &user).unwrap();
+            assert_eq!(1, alerts.len());
+            assert_eq!(duplicate, alerts[0].1);
+            assert_eq!(AlertReason::Duplicate.as_str(), alerts[0].0.reason);
+
+            Ok(())
+        });
+    }
+
+    // Tests that expenses with unusually high amounts are flagged.
+    #[test]
+    fn test_unusual_amount() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = create_test_category(&conn, &user);
+
+            // Without enough history no amount is considered unusual.
+            for (day, amount) in ["9.99", "10.99", "9.49", "10.49"].iter().enumerate() {
+                let date = format!("2020-01-{:02}", day + 1);
+                create_expense(&conn, &user, &cat, amount, &date);
+            }
+            let high = create_expense(&conn, &user, &cat, "200.00", "2020-02-01");
+            assert!(get_pending(&conn, &user).unwrap().is_empty());
+
+            // Remove the high amount again and build up some more history.
+            crate::expense::delete(&conn, high.id).unwrap();
+            create_expense(&conn, &user, &cat, "10.19", "2020-02-02");
+            create_expense(&conn, &user, &cat, "9.79", "2020-02-03");
+
+            // An amount within the usual range is fine.
+            create_expense(&conn, &user, &cat, "11.49", "2020-02-04");
+            assert!(get_pending(&conn, &user).unwrap().is_empty());
+
+            // An amount far above the usual range is flagged.
+            let unusual = create_expense(&conn, &user, &cat, "49.99", "2020-02-05");
+            let alerts = get_pending(&conn, &user).unwrap();
+            assert_eq!(1, alerts.len());
+            assert_eq!(unusual, alerts[0].1);
+            assert_eq!(AlertReason::UnusualAmount.as_str(), alerts[0].0.reason);
+
+            Ok(())
+        });
+    }
+
+    // Tests that a price hike at a payee is flagged, even if the amount is usual for the category.
+    #[test]
+    fn test_unusual_amount_payee() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = create_test_category(&conn, &user);
+
+            // A category with a wide range of amounts, which also contains a streaming
+            // subscription.
+            for (description, amount) in &[
+                ("Cinema", "15.00"),
+                ("Concert hall", "45.00"),
+                ("Game store", "59.99"),
+                ("Bowling", "30.00"),
+            ] {
+                create_payee_expense(&conn, &user, &cat, description, amount, "2019-12-15");
+            }
+            for month in 1..=5 {
+                let date = format!("2020-{:02}-01", month);
+                create_payee_expense(&conn, &user, &cat, "PAYPAL *STREAMFLIX", "9.99", &date);
+            }
+
+            // The usual price is fine.
+            create_payee_expense(
+                &conn,
+                &user,
+                &cat,
+                "PAYPAL *STREAMFLIX",
+                "9.99",
+                "2020-06-01",
+            );
+            assert!(get_pending(&conn, &user).unwrap().is_empty());
+
+            // A payee with too little history is compared to the category, in which the amount is
+            // not unusual.
+            create_payee_expense(&conn, &user, &cat, "Concert hall", "60.00", "2020-06-10");
+            assert!(get_pending(&conn, &user).unwrap().is_empty());
+
+            // The price hike is well within the range of the category, but not of the payee.
+            let hike = create_payee_expense(
+                &conn,
+                &user,
+                &cat,
+                "PAYPAL *STREAMFLIX",
+                "12.99",
+                "2020-07-01",
+            );
+            let alerts = get_pending(&conn, &user).unwrap();
+            assert_eq!(1, alerts.len());
+            assert_eq!(hike, alerts[0].1);
+            assert_eq!(AlertReason::UnusualAmount.as_str(), alerts[0].0.reason);
+
+            Ok(())
+        });
+    }
+
+    // Tests that only expenses up to the date of an expense are used as its history.
+    #[test]
+    fn test_unusual_amount_uses_earlier_expenses() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = create_test_category(&conn, &user);
+            for (day, amount) in ["9.99", "10.99", "9.49", "10.49", "10.19"]
+                .iter()
+                .enumerate()
+            {
+                let date = format!("2020-02-{:02}", day + 1);
+                create_expense(&conn, &user, &cat, amount, &date);
+            }
+
+            // A high amount that is back-filled before these expenses has no history.
+            let backfilled = create_expense(&conn, &user, &cat, "49.99", "2020-01-15");
+            assert!(get_pending(&conn, &user).unwrap().is_empty());
+            crate::expense::delete(&conn, backfilled.id).unwrap();
+
+            // The same amount after these expenses is flagged.
+            let unusual = create_expense(&conn, &user, &cat, "49.99", "2020-02-15");
+            let alerts = get_pending(&conn, &user).unwrap();
+            assert_eq!(1, alerts.len());
+            assert_eq!(unusual, alerts[0].1);
+
+            Ok(())
+        });
+    }
+
+    // Tests resolve().
+    #[test]
+    fn test_resolve() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = create_test_category(&conn, &user);
+            for _ in 0..3 {
+                create_expense(&conn, &user, &cat, "5.00", "2020-05-01");
+            }
+            let alerts = get_pending(&conn, &user).unwrap();
+            assert_eq!(2, alerts.len());
+            assert_eq!(2, count_pending(&conn, &user).unwrap());
+            let (first, second) = (alerts[0].0.id, alerts[1].0.id);
+
+            // Other users cannot resolve the alerts.
+            let other_user = create_test_user(&conn, &config);
+            assert_eq!(0, count_pending(&conn, &other_user).unwrap());
+            assert_eq!(
+                ExpenseAlertErrorKind::NotFound(first),
+                resolve(&conn, &other_user, first, AlertStatus::Dismissed).unwrap_err()
+            );
+
+            // Resolved alerts are no longer pending.
+            let alert = resolve(&conn, &user, first, AlertStatus::Dismissed).unwrap();
+            assert_eq!(AlertStatus::Dismissed.as_str(), alert.status);
+            assert_eq!(1, count_pending(&conn, &user).unwrap());
+            let alert = resolve(&conn, &user, second, AlertStatus::Confirmed).unwrap();
+            assert_eq!(AlertStatus::Confirmed.as_str(), alert.status);
+            assert!(get_pending(&conn, &user).unwrap().is_empty());
+            assert_eq!(0, count_pending(&conn, &user).unwrap());
+
+            Ok(())
+        });
+    }
+
+    // Creates an expense with the given amount and date.
+    fn create_expense(
+        conn: &PgConnection,
+        user: &User,
+        cat: &Category,
+        amount: &str,
+        date: &str,
+    ) -> Expense {
+        let amount = Decimal::from_str(amount).unwrap();
+        let date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        crate::expense::create(conn, user, &amount, cat, None, Some(&date)).unwrap()
+    }
+
+    // Creates an expense at the payee in the given description with the given amount and date.
+    fn create_payee_expense(
+        conn: &PgConnection,
+        user: &User,
+        cat: &Category,
+        description: &str,
+        amount: &str,
+        date: &str,
+    ) -> Expense {
+        let amount = Decimal::from_str(amount).unwrap();
+        let date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        crate::expense::create(conn, user, &amount, cat, Some(description), Some(&date)).unwrap()
+    }
+}
@@ -0,0 +1,404 @@
+use super::schema::{
+    categories, expenses, monthly_category_totals, recurring_expense_occurrences,
+    recurring_expenses,
+};
+use super::user::User;
+use super::{AsyncError, ConnectionPool};
+use chrono::{Datelike, Duration, NaiveDate};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The projected spending in a category at the end of the month.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CategoryForecast {
+    pub category_id: i32,
+    pub category_name: String,
+    // The amount that has been spent so far this month.
+    pub spent: Decimal,
+    // The amount that will have been spent at the end of the month if spending continues at the
+    // same rate, including the recurring expenses that are due before the end of the month.
+    pub projected: Decimal,
+}
+
+// Possible errors thrown when forecasting spending.
+#[derive(Debug, PartialEq)]
+pub enum ForecastErrorKind {
+    // The spending of the current month could not be read due to a database error.
+    ReadFailed(diesel::result::Error),
+}
+
+impl fmt::Display for ForecastErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ForecastErrorKind::ReadFailed(ref err) => {
+                write!(
+                    f,
+                    "Database error when reading the monthly spending: {}",
+                    err
+                )
+            }
+        }
+    }
+}
+
+/// Returns the projected spending per category at the end of the month containing the given date,
+/// ordered from the highest to the lowest projection.
+///
+/// The projection extrapolates the amount spent between the start of the month and the given date
+/// to the full month. Recurring expenses are not extrapolated, instead the occurrences that are due
+/// after the given date and before the end of the month are added. Categories without any spending
+/// or upcoming recurring expenses this month are not included.
+pub fn get_forecast(
+    connection: &PgConnection,
+    user: &User,
+    date: &NaiveDate,
+) -> Result<Vec<CategoryForecast>, ForecastErrorKind> {
+    let month = NaiveDate::from_ymd(date.year(), date.month(), 1);
+    let month_end = NaiveDate::from_ymd(date.year(), date.month(), days_in_month(date));
+
+    // The category names and the amounts that were spent, recurring and upcoming, per category.
+    let mut categories: BTreeMap<i32, (String, Decimal, Decimal, Decimal)> = BTreeMap::new();
+    let zero = Decimal::new(0, 2);
+
+    let totals = monthly_category_totals::table
+        .inner_join(categories::table)
+        .filter(monthly_category_totals::user_id.eq(user.id))
+        .filter(monthly_category_totals::month.eq(month))
+        .select((
+            monthly_category_totals::category_id,
+            categories::name,
+            monthly_category_totals::total,
+        ))
+        .load::<(i32, String, Decimal)>(connection)
+        .map_err(ForecastErrorKind::ReadFailed)?;
+    for (category_id, category_name, spent) in totals {
+        categories.insert(category_id, (category_name, spent, zero, zero));
+    }
+
+    // The expenses of this month that belong to a recurring expense.
+    let recurring = expenses::table
+        .inner_join(recurring_expense_occurrences::table)
+        .filter(expenses::user_id.eq(user.id))
+        .filter(expenses::date.between(month, month_end))
+        .select((expenses::category_id, expenses::amount))
+        .load::<(i32, Decimal)>(connection)
+        .map_err(ForecastErrorKind::ReadFailed)?;
+    for (category_id, amount) in recurring {
+        if let Some(category) = categories.get_mut(&category_id) {
+            category.2 += amount;
+        }
+    }
+
+    // The occurrences of recurring expenses that are still due this month.
+    let upcoming = recurring_expenses::table
+        .inner_join(categories::table)
+        .filter(recurring_expenses::user_id.eq(user.id))
+        .filter(recurring_expenses::next_date.le(month_end))
+        .select((
+            recurring_expenses::category_id,
+            categories::name,
+            recurring_expenses::amount,
+            recurring_expenses::interval_days,
+            recurring_expenses::next_date,
+        ))
+        .load::<(i32, String, Decimal, i32, NaiveDate)>(connection)
+        .map_err(ForecastErrorKind::ReadFailed)?;
+    for (category_id, category_name, amount, interval_days, next_date) in upcoming {
+        let count = count_occurrences(&next_date, interval_days, date, &month_end);
+        if count > 0 {
+            let category =
+                categories
+                    .entry(category_id)
+                    .or_insert((category_name, zero, zero, zero));
+            category.3 += amount * Decimal::from(count);
+        }
+    }
+
+    let mut forecast: Vec<CategoryForecast> = categories
+        .into_iter()
+        .map(
+            |(category_id, (category_name, spent, recurring, upcoming))| CategoryForecast {
+                category_id,
+                category_name,
+                spent,
+                projected: project(&(spent - recurring), date) + recurring + upcoming,
+            },
+        )
+        .collect();
+    forecast.sort_by_key(|f| std::cmp::Reverse(f.projected));
+
+    Ok(forecast)
+}
+
+/// Returns the projected spending per category without blocking the async executor.
+pub async fn get_forecast_async(
+    pool: &ConnectionPool,
+    user: &User,
+    date: &NaiveDate,
+) -> Result<Vec<CategoryForecast>, AsyncError<ForecastErrorKind>> {
+    let (user, date) = (user.clone(), *date);
+    super::run(pool, move |connection| {
+        get_forecast(connection, &user, &date)
+    })
+    .await
+}
+
+// Extrapolates the amount spent between the start of the month and the given date to the full
+// month.
+fn project(spent: &Decimal, date: &NaiveDate) -> Decimal {
+    let projected = *spent * Decimal::from(days_in_month(date)) / Decimal::from(date.day());
+    projected.round_dp(2)
+}
+
+// Returns the number of occurrences of a recurring expense with the given next date and interval
+// that fall after the given date, up to and including the given end date.
+fn count_occurrences(
+    next_date: &NaiveDate,
+    interval_days: i32,
+    after: &NaiveDate,
+    end: &NaiveDate,
+) -> usize {
+    if interval_days <= 0 {
+        return 0;
+    }
+    let mut occurrence = *next_date;
+    let mut count = 0;
+    while occurrence <= *end {
+        if occurrence > *after {
+            count += 1;
+        }
+        occurrence += Duration::days(interval_days.into());
+    }
+    count
+}
+
+// Returns the number of days in the month containing the given date.
+fn days_in_month(date: &NaiveDate) -> u32 {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        month => (date.year(), month + 1),
+    };
+    NaiveDate::from_ymd(year, month, 1).pred().day()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_test::*;
+    use crate::{establish_connection, get_database_url};
+    use app::AppConfig;
+    use diesel::result::Error;
+    use std::str::FromStr;
+
+    // Tests get_forecast().
+    #[test]
+    fn test_get_forecast() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let date = NaiveDate::from_ymd(2020, 4, 10);
+
+            // Without expenses there is nothing to forecast.
+            assert!(get_forecast(&conn, &user, &date).unwrap().is_empty());
+
+            let groceries = crate::category::create(&conn, &user, "Groceries", None, None).unwrap();
+            let transport = crate::category::create(&conn, &user, "Transport", None, None).unwrap();
+            for (category, amount, date) in &[
+                (&groceries, "100.00", "2020-04-02"),
+                (&groceries, "40.00", "2020-04-09"),
+                (&transport, "50.00", "2020-04-05"),
+                // Expenses in other months are not taken into account.
+                (&groceries, "999.99", "2020-03-31"),
+                (&transport, "999.99", "2020-05-01"),
+            ] {
+                let amount = Decimal::from_str(amount).unwrap();
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+                crate::expense::create(&conn, &user, &amount, category, None, Some(&date)).unwrap();
+            }
+
+            let expected = vec![
+                CategoryForecast {
+                    category_id: groceries.id,
+                    category_name: "Groceries".to_string(),
+                    spent: Decimal::from_str("140.00").unwrap(),
+                    projected: Decimal::from_str("420.00").unwrap(),
+                },
+                CategoryForecast {
+                    category_id: transport.id,
+                    category_name: "Transport".to_string(),
+                    spent: Decimal::from_str("50.00").unwrap(),
+                    projected: Decimal::from_str("150.00").unwrap(),
+                },
+            ];
+            assert_eq!(expected, get_forecast(&conn, &user, &date).unwrap());
+
+            Ok(())
+        });
+    }
+
+    // Tests that recurring expenses are not extrapolated and upcoming occurrences are included.
+    #[test]
+    fn test_get_forecast_recurring() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let date = NaiveDate::from_ymd(2020, 4, 10);
+            let housing = crate::category::create(&conn, &user, "Housing", None, None).unwrap();
+            let groceries = crate::category::create(&conn, &user, "Groceries", None, None).unwrap();
+            let streaming = crate::category::create(&conn, &user, "Streaming", None, None).unwrap();
+
+            // The rent for this month has been paid already, the next payment is due next month.
+            let rent = create_recurring_expense(&conn, &user, &housing, "500.00", 30, "2020-05-01");
+            let amount = Decimal::from_str("500.00").unwrap();
+            let rent_date = NaiveDate::from_ymd(2020, 4, 1);
+            let expense =
+                crate::expense::create(&conn, &user, &amount, &housing, None, Some(&rent_date))
+                    .unwrap();
+            diesel::insert_into(recurring_expense_occurrences::table)
+                .values((
+                    recurring_expense_occurrences::expense_id.eq(expense.id),
+                    recurring_expense_occurrences::recurring_expense_id.eq(rent),
+                ))
+                .execute(&conn)
+                .unwrap();
+
+            // A weekly subscription that has not been charged yet this month. It is due on April
+            // 15, 22 and 29.
+            create_recurring_expense(&conn, &user, &streaming, "10.00", 7, "2020-04-08");
+
+            // Recurring expenses that are due after the end of the month are ignored.
+            create_recurring_expense(&conn, &user, &groceries, "75.00", 30, "2020-05-02");
+
+            let amount = Decimal::from_str("100.00").unwrap();
+            let groceries_date = NaiveDate::from_ymd(2020, 4, 2);
+            crate::expense::create(
+                &conn,
+                &user,
+                &amount,
+                &groceries,
+                None,
+                Some(&groceries_date),
+            )
+            .unwrap();
+
+            let expected = vec![
+                CategoryForecast {
+                    category_id: housing.id,
+                    category_name: "Housing".to_string(),
+                    spent: Decimal::from_str("500.00").unwrap(),
+                    projected: Decimal::from_str("500.00").unwrap(),
+                },
+                CategoryForecast {
+                    category_id: groceries.id,
+                    category_name: "Groceries".to_string(),
+                    spent: Decimal::from_str("100.00").unwrap(),
+                    projected: Decimal::from_str("300.00").unwrap(),
+                },
+                CategoryForecast {
+                    category_id: streaming.id,
+                    category_name: "Streaming".to_string(),
+                    spent: Decimal::from_str("0.00").unwrap(),
+                    projected: Decimal::from_str("30.00").unwrap(),
+                },
+            ];
+            assert_eq!(expected, get_forecast(&conn, &user, &date).unwrap());
+
+            Ok(())
+        });
+    }
+
+    // Tests count_occurrences().
+    #[test]
+    fn test_count_occurrences() {
+        let test_cases = [
+            // Occurrences on the given date are not upcoming, those on the end date are.
+            ("2020-04-10", 7, 2),
+            ("2020-04-11", 7, 3),
+            ("2020-04-03", 7, 2),
+            ("2020-04-30", 30, 1),
+            ("2020-05-01", 7, 0),
+            ("2020-03-01", 30, 1),
+            ("2020-04-20", 0, 0),
+        ];
+
+        let after = NaiveDate::from_ymd(2020, 4, 10);
+        let end = NaiveDate::from_ymd(2020, 4, 30);
+        for (next_date, interval_days, expected) in &test_cases {
+            let next_date = NaiveDate::parse_from_str(next_date, "%Y-%m-%d").unwrap();
+            assert_eq!(
+                *expected,
+                count_occurrences(&next_date, *interval_days, &after, &end)
+            );
+        }
+    }
+
+    // Creates a recurring expense and returns its ID.
+    fn create_recurring_expense(
+        conn: &PgConnection,
+        user: &User,
+        category: &crate::category::Category,
+        amount: &str,
+        interval_days: i32,
+        next_date: &str,
+    ) -> i32 {
+        diesel::insert_into(recurring_expenses::table)
+            .values((
+                recurring_expenses::user_id.eq(user.id),
+                recurring_expenses::category_id.eq(category.id),
+                recurring_expenses::description.eq("Recurring"),
+                recurring_expenses::amount.eq(Decimal::from_str(amount).unwrap()),
+                recurring_expenses::interval_days.eq(interval_days),
+                recurring_expenses::next_date
+                    .eq(NaiveDate::parse_from_str(next_date, "%Y-%m-%d").unwrap()),
+            ))
+            .returning(recurring_expenses::id)
+            .get_result(conn)
+            .unwrap()
+    }
+
+    // Tests project().
+    #[test]
+    fn test_project() {
+        let test_cases = [
+            // On the last day of the month the projection equals the amount spent.
+            ("100.00", "2020-04-30", "100.00"),
+            ("100.00", "2020-04-01", "3000.00"),
+            ("100.00", "2020-04-15", "200.00"),
+            ("10.00", "2020-01-03", "103.33"),
+            ("0.01", "2020-02-29", "0.01"),
+            ("0.01", "2020-02-01", "0.29"),
+        ];
+
+        for (spent, date, expected) in &test_cases {
+            let spent = Decimal::from_str(spent).unwrap();
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+            let expected = Decimal::from_str(expected).unwrap();
+            assert_eq!(expected, project(&spent, &date));
+        }
+    }
+
+    // Tests days_in_month().
+    #[test]
+    fn test_days_in_month() {
+        let test_cases = [
+            ("2020-01-15", 31),
+            ("2019-02-01", 28),
+            ("2020-02-29", 29),
+            ("2020-04-30", 30),
+            ("2020-12-31", 31),
+        ];
+
+        for (date, expected) in &test_cases {
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+            assert_eq!(*expected, days_in_month(&date));
+        }
+    }
+}
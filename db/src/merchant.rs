@@ -0,0 +1,420 @@
+use super::schema::merchant_aliases::dsl;
+use super::user::User;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::DatabaseError;
+use serde::Serialize;
+use std::fmt;
+
+// Words that banks put in front of the merchant name to indicate how the payment was made.
+const PAYMENT_METHOD_KEYWORDS: [&str; 8] = [
+    "CARD",
+    "CONTACTLESS",
+    "CREDIT",
+    "DEBIT",
+    "MASTERCARD",
+    "POS",
+    "PURCHASE",
+    "VISA",
+];
+
+// Payment processors that prefix the merchant name followed by an asterisk, e.g. "PAYPAL *SPOTIFY".
+const PAYMENT_PROCESSORS: [&str; 4] = ["IZ", "PAYPAL", "SQ", "SUMUP"];
+
+// Well known merchants that appear under various names on bank statements. More specific prefixes
+// should come before less specific ones.
+const KNOWN_MERCHANTS: [(&str, &str); 8] = [
+    ("AMAZON", "Amazon"),
+    ("AMZN", "Amazon"),
+    ("APPLE.COM", "Apple"),
+    ("GOOGLE", "Google"),
+    ("NETFLIX", "Netflix"),
+    ("SPOTIFY", "Spotify"),
+    ("UBER EATS", "Uber Eats"),
+    ("UBER", "Uber"),
+];
+
+/// A user defined rule that maps descriptions containing the pattern to a merchant name.
+#[derive(Clone, Debug, PartialEq, Queryable, Serialize)]
+pub struct MerchantAlias {
+    pub id: i32,
+    pub user_id: i32,
+    // The text to look for in the description. This is stored in uppercase.
+    pub pattern: String,
+    pub name: String,
+}
+
+// Possible errors thrown when handling merchant aliases.
+#[derive(Debug, PartialEq)]
+pub enum MerchantErrorKind {
+    // An alias with the given pattern already exists.
+    AliasAlreadyExists(String),
+    // An alias with the given pattern does not exist.
+    AliasNotFound(String),
+    // A database error occurred.
+    DatabaseError(diesel::result::Error),
+    // Some required data is missing.
+    MissingData(String),
+}
+
+impl fmt::Display for MerchantErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MerchantErrorKind::AliasAlreadyExists(ref pattern) => {
+                write!(f, "An alias for '{}' already exists", pattern)
+            }
+            MerchantErrorKind::AliasNotFound(ref pattern) => {
+                write!(f, "Alias for '{}' not found", pattern)
+            }
+            MerchantErrorKind::DatabaseError(ref err) => write!(f, "Database error: {}", err),
+            MerchantErrorKind::MissingData(ref err) => write!(f, "Missing data for field: {}", err),
+        }
+    }
+}
+
+/// Creates an alias that maps all descriptions containing the given pattern to the given merchant
+/// name. The pattern is case insensitive.
+pub fn create_alias(
+    connection: &PgConnection,
+    user: &User,
+    pattern: &str,
+    name: &str,
+) -> Result<MerchantAlias, MerchantErrorKind> {
+    let pattern = pattern.trim().to_uppercase();
+    if pattern.is_empty() {
+        return Err(MerchantErrorKind::MissingData("pattern".to_string()));
+    }
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(MerchantErrorKind::MissingData("name".to_string()));
+    }
+
+    let result = diesel::insert_into(dsl::merchant_aliases)
+        .values((
+            dsl::user_id.eq(user.id),
+            dsl::pattern.eq(&pattern),
+            dsl::name.eq(name),
+        ))
+        .get_result(connection);
+
+    // Convert a UniqueViolation to a more informative AliasAlreadyExists error.
+    if let Err(DatabaseError(UniqueViolation, _)) = result {
+        return Err(MerchantErrorKind::AliasAlreadyExists(pattern));
+    }
+
+    result.map_err(MerchantErrorKind::DatabaseError)
+}
+
+/// Deletes the alias with the given pattern.
+pub fn delete_alias(
+    connection: &PgConnection,
+    user: &User,
+    pattern: &str,
+) -> Result<(), MerchantErrorKind> {
+    let pattern = pattern.trim().to_uppercase();
+    let count = diesel::delete(
+        dsl::merchant_aliases
+            .filter(dsl::user_id.eq(user.id))
+            .filter(dsl::pattern.eq(&pattern)),
+    )
+    .execute(connection)
+    .map_err(MerchantErrorKind::DatabaseError)?;
+
+    match count {
+        0 => Err(MerchantErrorKind::AliasNotFound(pattern)),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the aliases of the given user, ordered by pattern.
+pub fn get_aliases(
+    connection: &PgConnection,
+    user: &User,
+) -> Result<Vec<MerchantAlias>, MerchantErrorKind> {
+    load_aliases(connection, user).map_err(MerchantErrorKind::DatabaseError)
+}
+
+// Loads the aliases of the given user, ordered by pattern.
+fn load_aliases(connection: &PgConnection, user: &User) -> QueryResult<Vec<MerchantAlias>> {
+    dsl::merchant_aliases
+        .filter(dsl::user_id.eq(user.id))
+        .order(dsl::pattern)
+        .load::<MerchantAlias>(connection)
+}
+
+/// Turns descriptions from bank statements into merchant names, so that expenses from the same
+/// merchant can be recognized even if the descriptions contain card numbers or references.
+///
+/// The aliases of the user are checked first. If several of them match, the alias with the longest
+/// pattern is used, since it is the most specific one. If none of them match, payment method
+/// details, payment processors and references are stripped, and well known merchants are
+/// recognized.
+#[derive(Clone, Debug, Default)]
+pub struct Normalizer {
+    aliases: Vec<MerchantAlias>,
+}
+
+impl Normalizer {
+    /// Returns a normalizer that uses the given aliases.
+    pub fn new(aliases: Vec<MerchantAlias>) -> Normalizer {
+        Normalizer { aliases }
+    }
+
+    /// Returns a normalizer that uses the aliases of the given user.
+    pub fn load(connection: &PgConnection, user: &User) -> QueryResult<Normalizer> {
+        Ok(Normalizer::new(load_aliases(connection, user)?))
+    }
+
+    /// Returns the merchant name for the given description.
+    pub fn normalize(&self, description: &str) -> String {
+        let description = description.trim().to_uppercase();
+
+        if let Some(alias) = self
+            .aliases
+            .iter()
+            .filter(|alias| description.contains(&alias.pattern))
+            .max_by_key(|alias| alias.pattern.len())
+        {
+            return alias.name.clone();
+        }
+
+        let merchant = strip_reference(strip_payment_processor(strip_payment_method(&description)));
+        let merchant = strip_trailing_numbers(merchant);
+        if merchant.is_empty() {
+            return title_case(&description);
+        }
+
+        match KNOWN_MERCHANTS
+            .iter()
+            .find(|(prefix, _)| starts_with_word(merchant, prefix))
+        {
+            Some((_, name)) => name.to_string(),
+            None => title_case(merchant),
+        }
+    }
+}
+
+// Removes the payment method and card number in front of the merchant name, e.g. "CARD 1234".
+fn strip_payment_method(description: &str) -> &str {
+    let mut remainder = description.trim_start();
+    while let Some(word) = remainder.split_whitespace().next() {
+        if !PAYMENT_METHOD_KEYWORDS.contains(&word) && !is_card_number(word) {
+            break;
+        }
+        remainder = remainder[word.len()..].trim_start();
+    }
+    remainder
+}
+
+// Removes the name of a payment processor in front of the merchant name, e.g. "PAYPAL *".
+fn strip_payment_processor(description: &str) -> &str {
+    for processor in PAYMENT_PROCESSORS.iter() {
+        if let Some(remainder) = description.strip_prefix(processor) {
+            if let Some(merchant) = remainder.trim_start().strip_prefix('*') {
+                return merchant.trim_start();
+            }
+        }
+    }
+    description
+}
+
+// Removes a reference that is appended to the merchant name with an asterisk, e.g. "*AB12".
+fn strip_reference(description: &str) -> &str {
+    match description.find('*') {
+        Some(pos) if pos > 0 => description[..pos].trim_end(),
+        _ => description,
+    }
+}
+
+// Removes trailing words containing digits, like store numbers or transaction IDs.
+fn strip_trailing_numbers(description: &str) -> &str {
+    let mut remainder = description.trim_end();
+    while let Some(word) = remainder.split_whitespace().last() {
+        if !word.chars().any(|c| c.is_ascii_digit()) {
+            break;
+        }
+        remainder = remainder[..remainder.len() - word.len()].trim_end();
+    }
+    remainder
+}
+
+// Returns whether the given word looks like a (partially masked) card number, e.g. "1234" or
+// "XXXX1234".
+fn is_card_number(word: &str) -> bool {
+    word.chars().any(|c| c.is_ascii_digit())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '*' || c == 'X')
+}
+
+// Returns whether the description starts with the given prefix followed by the end of a word.
+fn starts_with_word(description: &str, prefix: &str) -> bool {
+    match description.strip_prefix(prefix) {
+        Some(remainder) => match remainder.chars().next() {
+            Some(c) => !c.is_alphanumeric(),
+            None => true,
+        },
+        None => false,
+    }
+}
+
+// Capitalizes the first letter of every word and lowercases the rest.
+fn title_case(description: &str) -> String {
+    description
+        .split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_test::*;
+    use crate::{establish_connection, get_database_url};
+    use app::AppConfig;
+    use diesel::result::Error;
+
+    // Tests Normalizer::normalize() without aliases.
+    #[test]
+    fn test_normalize() {
+        let test_cases = [
+            ("CARD 1234 AMZN MKTP UK*AB12", "Amazon"),
+            ("Amazon.co.uk", "Amazon"),
+            ("VISA DEBIT XXXX5678 TESCO STORES 3021", "Tesco Stores"),
+            ("CONTACTLESS ****4321 PRET A MANGER", "Pret A Manger"),
+            ("PAYPAL *SPOTIFY", "Spotify"),
+            ("PAYPAL*NETFLIX.COM 866-579-7172", "Netflix"),
+            ("SQ *BLUE BOTTLE COFFEE", "Blue Bottle Coffee"),
+            ("UBER EATS HELP.UBER.COM", "Uber Eats"),
+            ("UBERLY CAFE", "Uberly Cafe"),
+            ("  rent  ", "Rent"),
+            // Descriptions that consist only of numbers are kept as they are.
+            ("CARD 1234", "Card 1234"),
+            ("", ""),
+        ];
+
+        let normalizer = Normalizer::default();
+        for (description, expected) in &test_cases {
+            assert_eq!(*expected, normalizer.normalize(description));
+        }
+    }
+
+    // Tests that aliases take precedence over the built-in rules.
+    #[test]
+    fn test_normalize_with_aliases() {
+        let alias = |pattern: &str, name: &str| MerchantAlias {
+            id: 1,
+            user_id: 1,
+            pattern: pattern.to_string(),
+            name: name.to_string(),
+        };
+        let normalizer = Normalizer::new(vec![
+            alias("AMZN DIGITAL", "Kindle"),
+            alias("TFL.GOV", "Transport for London"),
+        ]);
+
+        assert_eq!(
+            "Kindle",
+            normalizer.normalize("CARD 1234 amzn digital*XY99")
+        );
+        assert_eq!(
+            "Amazon",
+            normalizer.normalize("CARD 1234 AMZN MKTP UK*AB12")
+        );
+        assert_eq!(
+            "Transport for London",
+            normalizer.normalize("TFL.GOV.UK/CP 0343 222 1234")
+        );
+    }
+
+    // Tests that the most specific alias is used if several aliases match.
+    #[test]
+    fn test_normalize_with_overlapping_aliases() {
+        let alias = |pattern: &str, name: &str| MerchantAlias {
+            id: 1,
+            user_id: 1,
+            pattern: pattern.to_string(),
+            name: name.to_string(),
+        };
+
+        // The aliases are ordered by pattern, so the shorter pattern comes first.
+        let normalizer = Normalizer::new(vec![
+            alias("AMZN", "Amazon"),
+            alias("AMZN DIGITAL", "Kindle"),
+        ]);
+        assert_eq!("Kindle", normalizer.normalize("AMZN DIGITAL*XY99"));
+        assert_eq!("Amazon", normalizer.normalize("AMZN MKTP UK*AB12"));
+
+        // The order of the aliases does not matter.
+        let normalizer = Normalizer::new(vec![
+            alias("AMZN DIGITAL", "Kindle"),
+            alias("AMZN", "Amazon"),
+        ]);
+        assert_eq!("Kindle", normalizer.normalize("AMZN DIGITAL*XY99"));
+        assert_eq!("Amazon", normalizer.normalize("AMZN MKTP UK*AB12"));
+    }
+
+    // Tests creating, reading and deleting aliases.
+    #[test]
+    fn test_aliases() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            assert!(get_aliases(&conn, &user).unwrap().is_empty());
+
+            // The pattern and name are required.
+            assert_eq!(
+                MerchantErrorKind::MissingData("pattern".to_string()),
+                create_alias(&conn, &user, " ", "Kindle").unwrap_err()
+            );
+            assert_eq!(
+                MerchantErrorKind::MissingData("name".to_string()),
+                create_alias(&conn, &user, "AMZN DIGITAL", "").unwrap_err()
+            );
+
+            // Patterns are stored in uppercase.
+            let alias = create_alias(&conn, &user, " amzn digital ", "Kindle").unwrap();
+            assert_eq!("AMZN DIGITAL", alias.pattern);
+            assert_eq!("Kindle", alias.name);
+
+            // Other users can use the same pattern.
+            let other_user = create_test_user(&conn, &config);
+            create_alias(&conn, &other_user, "AMZN DIGITAL", "Amazon").unwrap();
+
+            let normalizer = Normalizer::load(&conn, &user).unwrap();
+            assert_eq!("Kindle", normalizer.normalize("AMZN DIGITAL*XY99"));
+            assert_eq!(vec![alias], get_aliases(&conn, &user).unwrap());
+
+            delete_alias(&conn, &user, "amzn digital").unwrap();
+            assert!(get_aliases(&conn, &user).unwrap().is_empty());
+            assert_eq!(
+                MerchantErrorKind::AliasNotFound("AMZN DIGITAL".to_string()),
+                delete_alias(&conn, &user, "AMZN DIGITAL").unwrap_err()
+            );
+            assert_eq!(1, get_aliases(&conn, &other_user).unwrap().len());
+
+            // Patterns are unique per user. This is checked last since the failed query aborts the
+            // transaction.
+            create_alias(&conn, &user, "AMZN DIGITAL", "Kindle").unwrap();
+            assert_eq!(
+                MerchantErrorKind::AliasAlreadyExists("AMZN DIGITAL".to_string()),
+                create_alias(&conn, &user, "Amzn Digital", "Amazon").unwrap_err()
+            );
+
+            Ok(())
+        });
+    }
+}
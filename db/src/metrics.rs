@@ -0,0 +1,152 @@
+use super::ConnectionPool;
+use r2d2::event::{CheckoutEvent, HandleEvent, TimeoutEvent};
+use std::any::type_name;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// Counters that are updated by the event handler of the connection pool.
+static CHECKOUTS: AtomicU64 = AtomicU64::new(0);
+static CHECKOUT_TIMEOUTS: AtomicU64 = AtomicU64::new(0);
+static CHECKOUT_WAIT_MICROSECONDS: AtomicU64 = AtomicU64::new(0);
+
+// The number of database operations that exceeded the slow query threshold.
+static SLOW_QUERIES: AtomicU64 = AtomicU64::new(0);
+
+// The number of milliseconds after which database operations are logged. 0 disables logging.
+static SLOW_QUERY_THRESHOLD_MILLISECONDS: AtomicU64 = AtomicU64::new(0);
+
+/// A snapshot of the state of a connection pool and the database operations that were executed.
+///
+/// The counters are cumulative since the application started and are shared between all pools.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolMetrics {
+    /// The maximum number of connections the pool can manage.
+    pub max_connections: u32,
+    /// The number of connections that are currently checked out.
+    pub in_use_connections: u32,
+    /// The number of connections that are open but not checked out.
+    pub idle_connections: u32,
+    /// The number of times a connection was checked out.
+    pub checkouts: u64,
+    /// The number of times no connection could be checked out before the timeout expired.
+    pub checkout_timeouts: u64,
+    /// The total time spent waiting for a connection to be checked out.
+    pub checkout_wait_time: Duration,
+    /// The number of database operations that exceeded the slow query threshold.
+    pub slow_queries: u64,
+}
+
+/// Returns the current metrics of the given connection pool.
+pub fn pool_metrics(pool: &ConnectionPool) -> PoolMetrics {
+    let state = pool.state();
+    PoolMetrics {
+        max_connections: pool.max_size(),
+        in_use_connections: state.connections - state.idle_connections,
+        idle_connections: state.idle_connections,
+        checkouts: CHECKOUTS.load(Ordering::Relaxed),
+        checkout_timeouts: CHECKOUT_TIMEOUTS.load(Ordering::Relaxed),
+        checkout_wait_time: Duration::from_micros(
+            CHECKOUT_WAIT_MICROSECONDS.load(Ordering::Relaxed),
+        ),
+        slow_queries: SLOW_QUERIES.load(Ordering::Relaxed),
+    }
+}
+
+/// Sets the duration after which database operations are logged as being slow. A duration of 0
+/// disables logging.
+pub fn set_slow_query_threshold(threshold: Duration) {
+    SLOW_QUERY_THRESHOLD_MILLISECONDS.store(threshold.as_millis() as u64, Ordering::Relaxed);
+}
+
+// Executes the given database operation and logs a warning if it exceeds the slow query threshold.
+// The context indicates where the operation was called from.
+pub(crate) fn log_slow_query<F, T>(context: &str, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let start = Instant::now();
+    let result = f();
+    let duration = start.elapsed();
+
+    let threshold = SLOW_QUERY_THRESHOLD_MILLISECONDS.load(Ordering::Relaxed);
+    if threshold > 0 && duration >= Duration::from_millis(threshold) {
+        SLOW_QUERIES.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "Slow database operation in {} took {} ms",
+            context,
+            duration.as_millis()
+        );
+    }
+
+    result
+}
+
+// Returns the path of the function in which the given closure type is defined, e.g.
+// `db::user::create_async`.
+pub(crate) fn get_context<F>() -> &'static str {
+    let name = type_name::<F>();
+    name.find("::{{closure}}")
+        .map(|pos| &name[..pos])
+        .unwrap_or(name)
+}
+
+// Event handler that keeps track of the checkouts from the connection pool.
+#[derive(Debug)]
+pub(crate) struct PoolEventHandler;
+
+impl HandleEvent for PoolEventHandler {
+    fn handle_checkout(&self, event: CheckoutEvent) {
+        CHECKOUTS.fetch_add(1, Ordering::Relaxed);
+        CHECKOUT_WAIT_MICROSECONDS
+            .fetch_add(event.duration().as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn handle_timeout(&self, _event: TimeoutEvent) {
+        CHECKOUT_TIMEOUTS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_test_connection_pool, get_database_url};
+
+    // Tests pool_metrics().
+    #[test]
+    fn test_pool_metrics() {
+        let pool = create_test_connection_pool(&get_database_url()).unwrap();
+        let checkouts = pool_metrics(&pool).checkouts;
+
+        // A connection that is checked out is counted as being in use.
+        let connection = pool.get().unwrap();
+        let metrics = pool_metrics(&pool);
+        assert_eq!(pool.max_size(), metrics.max_connections);
+        assert_eq!(1, metrics.in_use_connections);
+        assert!(metrics.checkouts > checkouts);
+
+        // When the connection is returned to the pool it becomes idle.
+        drop(connection);
+        let metrics = pool_metrics(&pool);
+        assert_eq!(0, metrics.in_use_connections);
+        assert!(metrics.idle_connections > 0);
+    }
+
+    // Tests get_context().
+    #[test]
+    fn test_get_context() {
+        let closure = || ();
+        assert_eq!(
+            "db::metrics::tests::test_get_context",
+            get_context_of(&closure)
+        );
+        assert_eq!(
+            "db::check_schema_version",
+            get_context_of(&crate::check_schema_version)
+        );
+    }
+
+    // Returns the context of the given value, inferring its type.
+    fn get_context_of<F>(_: &F) -> &'static str {
+        get_context::<F>()
+    }
+}
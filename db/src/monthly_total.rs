@@ -0,0 +1,334 @@
+use super::expense::{Expense, BULK_INSERT_CHUNK_SIZE};
+use super::schema::monthly_category_totals::dsl;
+use super::user::User;
+use chrono::{Datelike, NaiveDate};
+use diesel::pg::upsert::excluded;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The total amount spent in a category during a month. These totals are kept up to date whenever
+/// expenses are created or deleted, so reports don't need to sum all expenses.
+#[derive(Clone, Debug, PartialEq, Queryable, Serialize)]
+pub struct MonthlyCategoryTotal {
+    pub category_id: i32,
+    // The first day of the month.
+    pub month: NaiveDate,
+    pub user_id: i32,
+    pub total: Decimal,
+    pub expense_count: i32,
+}
+
+// Possible errors thrown when handling monthly totals.
+#[derive(Debug, PartialEq)]
+pub enum MonthlyTotalErrorKind {
+    // The monthly totals could not be read due to a database error.
+    ReadFailed(diesel::result::Error),
+    // The monthly totals could not be rebuilt due to a database error.
+    RefreshFailed(diesel::result::Error),
+}
+
+impl fmt::Display for MonthlyTotalErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MonthlyTotalErrorKind::ReadFailed(ref err) => {
+                write!(f, "Database error when reading monthly totals: {}", err)
+            }
+            MonthlyTotalErrorKind::RefreshFailed(ref err) => {
+                write!(f, "Database error when refreshing monthly totals: {}", err)
+            }
+        }
+    }
+}
+
+/// Returns the totals per category of the given user for the month containing the given date.
+pub fn get_totals(
+    connection: &PgConnection,
+    user: &User,
+    month: &NaiveDate,
+) -> Result<Vec<MonthlyCategoryTotal>, MonthlyTotalErrorKind> {
+    dsl::monthly_category_totals
+        .filter(dsl::user_id.eq(user.id))
+        .filter(dsl::month.eq(first_day_of_month(month)))
+        .order(dsl::category_id)
+        .load::<MonthlyCategoryTotal>(connection)
+        .map_err(MonthlyTotalErrorKind::ReadFailed)
+}
+
+/// Rebuilds all monthly totals from the expenses. The totals are maintained automatically, so this
+/// is only needed to repair them if they went out of sync, e.g. after editing expenses directly in
+/// the database.
+pub fn refresh(connection: &PgConnection) -> Result<(), MonthlyTotalErrorKind> {
+    connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::delete(dsl::monthly_category_totals).execute(connection)?;
+            diesel::sql_query(
+                "INSERT INTO monthly_category_totals (category_id, month, user_id, total, expense_count)
+                SELECT category_id, DATE_TRUNC('month', date)::DATE, user_id, SUM(amount), COUNT(*)
+                FROM expenses
+                GROUP BY category_id, DATE_TRUNC('month', date), user_id",
+            )
+            .execute(connection)?;
+            Ok(())
+        })
+        .map_err(MonthlyTotalErrorKind::RefreshFailed)
+}
+
+// Adds the given newly created expenses to the monthly totals.
+pub(crate) fn add_expenses(connection: &PgConnection, expenses: &[Expense]) -> QueryResult<()> {
+    update_totals(connection, expenses, false)
+}
+
+// Subtracts the given deleted expenses from the monthly totals.
+pub(crate) fn remove_expenses(connection: &PgConnection, expenses: &[Expense]) -> QueryResult<()> {
+    update_totals(connection, expenses, true)?;
+
+    // Remove the totals of months that no longer have any expenses, so reports don't list
+    // categories with a total of zero.
+    let category_ids: Vec<i32> = expenses.iter().map(|e| e.category_id).collect();
+    diesel::delete(
+        dsl::monthly_category_totals
+            .filter(dsl::category_id.eq_any(category_ids))
+            .filter(dsl::expense_count.le(0)),
+    )
+    .execute(connection)?;
+    Ok(())
+}
+
+// Adds or subtracts the amounts of the given expenses to the totals of their category and month.
+fn update_totals(
+    connection: &PgConnection,
+    expenses: &[Expense],
+    subtract: bool,
+) -> QueryResult<()> {
+    // Group the expenses by category and month so every total is only updated once.
+    let mut changes: BTreeMap<(i32, NaiveDate), (i32, Decimal, i32)> = BTreeMap::new();
+    for expense in expenses {
+        let change = changes
+            .entry((expense.category_id, first_day_of_month(&expense.date)))
+            .or_insert((expense.user_id, Decimal::new(0, 2), 0));
+        change.1 += expense.amount;
+        change.2 += 1;
+    }
+
+    let values: Vec<_> = changes
+        .into_iter()
+        .map(|((category_id, month), (user_id, total, expense_count))| {
+            let (total, expense_count) = if subtract {
+                (-total, -expense_count)
+            } else {
+                (total, expense_count)
+            };
+            (
+                dsl::category_id.eq(category_id),
+                dsl::month.eq(month),
+                dsl::user_id.eq(user_id),
+                dsl::total.eq(total),
+                dsl::expense_count.eq(expense_count),
+            )
+        })
+        .collect();
+
+    // Every total takes 5 bind parameters, fewer than an expense, so the chunks of
+    // `expense::create_many()` also stay within the limit of PostgreSQL.
+    for chunk in values.chunks(BULK_INSERT_CHUNK_SIZE) {
+        diesel::insert_into(dsl::monthly_category_totals)
+            .values(chunk)
+            .on_conflict((dsl::category_id, dsl::month))
+            .do_update()
+            .set((
+                dsl::total.eq(dsl::total + excluded(dsl::total)),
+                dsl::expense_count.eq(dsl::expense_count + excluded(dsl::expense_count)),
+            ))
+            .execute(connection)?;
+    }
+    Ok(())
+}
+
+// Returns the first day of the month containing the given date.
+fn first_day_of_month(date: &NaiveDate) -> NaiveDate {
+    NaiveDate::from_ymd(date.year(), date.month(), 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::category::Category;
+    use crate::db_test::*;
+    use crate::{establish_connection, get_database_url};
+    use app::AppConfig;
+    use diesel::result::Error;
+    use std::str::FromStr;
+
+    // Tests that the monthly totals are updated when expenses are created and deleted.
+    #[test]
+    fn test_totals_are_maintained() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat1 = create_test_category(&conn, &user);
+            let cat2 = create_test_category(&conn, &user);
+            let may = NaiveDate::from_ymd(2020, 5, 1);
+            let june = NaiveDate::from_ymd(2020, 6, 1);
+
+            // Initially there are no totals.
+            assert!(get_totals(&conn, &user, &may).unwrap().is_empty());
+
+            let expenses = [
+                create_expense(&conn, &user, &cat1, "10.00", "2020-05-01"),
+                create_expense(&conn, &user, &cat1, "2.50", "2020-05-31"),
+                create_expense(&conn, &user, &cat2, "7.25", "2020-05-15"),
+                create_expense(&conn, &user, &cat1, "99.99", "2020-06-01"),
+            ];
+
+            assert_totals(
+                &conn,
+                &user,
+                &may,
+                &[(&cat1, "12.50", 2), (&cat2, "7.25", 1)],
+            );
+            assert_totals(&conn, &user, &june, &[(&cat1, "99.99", 1)]);
+
+            // The totals can be requested using any day of the month.
+            let may_15 = NaiveDate::from_ymd(2020, 5, 15);
+            assert_eq!(
+                get_totals(&conn, &user, &may).unwrap(),
+                get_totals(&conn, &user, &may_15).unwrap()
+            );
+
+            // Deleting an expense subtracts it from the total.
+            crate::expense::delete(&conn, expenses[0].id).unwrap();
+            assert_totals(
+                &conn,
+                &user,
+                &may,
+                &[(&cat1, "2.50", 1), (&cat2, "7.25", 1)],
+            );
+
+            // When the last expense of a month is deleted, the total is removed.
+            crate::expense::delete(&conn, expenses[2].id).unwrap();
+            assert_totals(&conn, &user, &may, &[(&cat1, "2.50", 1)]);
+
+            // Totals of other months are not affected.
+            assert_totals(&conn, &user, &june, &[(&cat1, "99.99", 1)]);
+
+            // The totals of other users are not returned.
+            let other_user = create_test_user(&conn, &config);
+            assert!(get_totals(&conn, &other_user, &may).unwrap().is_empty());
+
+            Ok(())
+        });
+    }
+
+    // Tests that the totals are updated when more expenses are created at once than fit in a single
+    // statement.
+    #[test]
+    fn test_totals_of_many_expenses() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat1 = create_test_category(&conn, &user);
+            let cat2 = create_test_category(&conn, &user);
+
+            // Create two expenses in every category and month for more months than fit in a chunk.
+            let months: Vec<NaiveDate> = (0..BULK_INSERT_CHUNK_SIZE as i32 + 2)
+                .map(|i| NaiveDate::from_ymd(2010 + i / 12, (i % 12) as u32 + 1, 1))
+                .collect();
+            let mut new_expenses = vec![];
+            for month in &months {
+                for cat in &[&cat1, &cat2] {
+                    for day in &[1, 28] {
+                        new_expenses.push(crate::expense::NewExpense {
+                            amount: Decimal::from_str("1.25").unwrap(),
+                            category: cat,
+                            description: None,
+                            date: Some(month.with_day(*day).unwrap()),
+                            import_reference: None,
+                        });
+                    }
+                }
+            }
+            crate::expense::create_many(&conn, &user, &new_expenses).unwrap();
+
+            for month in &months {
+                assert_totals(
+                    &conn,
+                    &user,
+                    month,
+                    &[(&cat1, "2.50", 2), (&cat2, "2.50", 2)],
+                );
+            }
+
+            Ok(())
+        });
+    }
+
+    // Tests that refresh() rebuilds the monthly totals.
+    #[test]
+    fn test_refresh() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = create_test_category(&conn, &user);
+            let may = NaiveDate::from_ymd(2020, 5, 1);
+            create_expense(&conn, &user, &cat, "10.00", "2020-05-01");
+            create_expense(&conn, &user, &cat, "5.00", "2020-05-02");
+
+            // Simulate totals that went out of sync.
+            diesel::update(dsl::monthly_category_totals.filter(dsl::user_id.eq(user.id)))
+                .set((dsl::total.eq(Decimal::new(1, 2)), dsl::expense_count.eq(7)))
+                .execute(&conn)?;
+            assert_totals(&conn, &user, &may, &[(&cat, "0.01", 7)]);
+
+            refresh(&conn).unwrap();
+            assert_totals(&conn, &user, &may, &[(&cat, "15.00", 2)]);
+
+            Ok(())
+        });
+    }
+
+    // Creates an expense with the given amount and date.
+    fn create_expense(
+        conn: &PgConnection,
+        user: &User,
+        cat: &Category,
+        amount: &str,
+        date: &str,
+    ) -> Expense {
+        let amount = Decimal::from_str(amount).unwrap();
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        crate::expense::create(conn, user, &amount, cat, None, Some(&date)).unwrap()
+    }
+
+    // Checks that the totals for the given month match the expected category totals and counts.
+    fn assert_totals(
+        conn: &PgConnection,
+        user: &User,
+        month: &NaiveDate,
+        expected: &[(&Category, &str, i32)],
+    ) {
+        let totals = get_totals(conn, user, month).unwrap();
+        let mut expected: Vec<(i32, Decimal, i32)> = expected
+            .iter()
+            .map(|(cat, total, count)| (cat.id, Decimal::from_str(total).unwrap(), *count))
+            .collect();
+        expected.sort();
+        let actual: Vec<(i32, Decimal, i32)> = totals
+            .iter()
+            .map(|t| (t.category_id, t.total, t.expense_count))
+            .collect();
+        assert_eq!(expected, actual);
+        assert!(totals
+            .iter()
+            .all(|t| t.user_id == user.id && t.month == *month));
+    }
+}
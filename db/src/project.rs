@@ -0,0 +1,398 @@
+use super::expense::Expense;
+use super::schema::projects::dsl;
+use super::schema::{categories, expenses, project_expenses};
+use super::user::User;
+use super::{AsyncError, ConnectionPool};
+use diesel::pg::upsert::excluded;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::DatabaseError;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A project or trip that groups expenses across categories, e.g. a holiday or a renovation.
+#[derive(Clone, Debug, PartialEq, Queryable, Serialize)]
+pub struct Project {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub budget: Option<Decimal>,
+}
+
+/// The amount spent on a project in a single category.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ProjectCategoryTotal {
+    pub category_id: i32,
+    pub category_name: String,
+    pub total: Decimal,
+}
+
+/// An overview of the spending on a project.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ProjectSummary {
+    pub project: Project,
+    // The totals per category, ordered by category name.
+    pub categories: Vec<ProjectCategoryTotal>,
+    pub total: Decimal,
+    // The part of the budget that has not been spent yet. This is negative if the project is over
+    // budget, and empty if the project has no budget.
+    pub remaining: Option<Decimal>,
+}
+
+// Possible errors thrown when handling projects.
+#[derive(Debug, PartialEq)]
+pub enum ProjectErrorKind {
+    // A database error occurred.
+    DatabaseError(diesel::result::Error),
+    // An expense was passed that belongs to a different user than the project.
+    ExpenseHasWrongUser,
+    // The budget should be greater than 0.
+    InvalidBudget,
+    // Some required data is missing.
+    MissingData(String),
+    // The project does not exist.
+    NotFound(i32),
+    // A project with the given name already exists.
+    ProjectAlreadyExists(String),
+}
+
+impl fmt::Display for ProjectErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProjectErrorKind::DatabaseError(ref err) => write!(f, "Database error: {}", err),
+            ProjectErrorKind::ExpenseHasWrongUser => {
+                write!(f, "Expense should be for the same user as the project")
+            }
+            ProjectErrorKind::InvalidBudget => {
+                write!(f, "Budget should be between 0.01 and 9999999.99")
+            }
+            ProjectErrorKind::MissingData(ref err) => write!(f, "Missing data for field: {}", err),
+            ProjectErrorKind::NotFound(ref id) => write!(f, "Project {} not found", id),
+            ProjectErrorKind::ProjectAlreadyExists(ref name) => {
+                write!(f, "The project '{}' already exists", name)
+            }
+        }
+    }
+}
+
+impl From<diesel::result::Error> for ProjectErrorKind {
+    fn from(e: diesel::result::Error) -> Self {
+        ProjectErrorKind::DatabaseError(e)
+    }
+}
+
+/// Creates a project with an optional budget.
+pub fn create(
+    connection: &PgConnection,
+    user: &User,
+    name: &str,
+    budget: Option<&Decimal>,
+) -> Result<Project, ProjectErrorKind> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ProjectErrorKind::MissingData("project name".to_string()));
+    }
+
+    if let Some(budget) = budget {
+        if *budget <= Decimal::new(0, 2) || *budget > Decimal::new(999_999_999, 2) {
+            return Err(ProjectErrorKind::InvalidBudget);
+        }
+    }
+
+    let result = diesel::insert_into(dsl::projects)
+        .values((
+            dsl::user_id.eq(user.id),
+            dsl::name.eq(name),
+            dsl::budget.eq(budget),
+        ))
+        .get_result(connection);
+
+    // Convert a UniqueViolation to a more informative ProjectAlreadyExists error.
+    if let Err(DatabaseError(UniqueViolation, _)) = result {
+        return Err(ProjectErrorKind::ProjectAlreadyExists(name.to_string()));
+    }
+
+    result.map_err(ProjectErrorKind::DatabaseError)
+}
+
+/// Retrieves the project with the given ID.
+pub fn read(connection: &PgConnection, id: i32) -> Option<Project> {
+    dsl::projects.find(id).first::<Project>(connection).ok()
+}
+
+/// Deletes the project with the given ID. The expenses in the project are not deleted.
+pub fn delete(connection: &PgConnection, id: i32) -> Result<(), ProjectErrorKind> {
+    let count = diesel::delete(dsl::projects.filter(dsl::id.eq(id))).execute(connection)?;
+
+    // Throw an error if nothing was deleted.
+    if count == 0 {
+        return Err(ProjectErrorKind::NotFound(id));
+    }
+
+    Ok(())
+}
+
+/// Returns the given user's projects, ordered by name.
+pub fn get_projects(
+    connection: &PgConnection,
+    user: &User,
+) -> Result<Vec<Project>, ProjectErrorKind> {
+    Ok(dsl::projects
+        .filter(dsl::user_id.eq(user.id))
+        .order(dsl::name)
+        .load::<Project>(connection)?)
+}
+
+/// Adds the given expense to the project. If the expense was part of another project, it is moved.
+pub fn add_expense(
+    connection: &PgConnection,
+    project: &Project,
+    expense: &Expense,
+) -> Result<(), ProjectErrorKind> {
+    if expense.user_id != project.user_id {
+        return Err(ProjectErrorKind::ExpenseHasWrongUser);
+    }
+
+    diesel::insert_into(project_expenses::table)
+        .values((
+            project_expenses::expense_id.eq(expense.id),
+            project_expenses::project_id.eq(project.id),
+        ))
+        .on_conflict(project_expenses::expense_id)
+        .do_update()
+        .set(project_expenses::project_id.eq(excluded(project_expenses::project_id)))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Removes the given expense from the project it is part of, if any.
+pub fn remove_expense(
+    connection: &PgConnection,
+    expense: &Expense,
+) -> Result<(), ProjectErrorKind> {
+    diesel::delete(project_expenses::table.filter(project_expenses::expense_id.eq(expense.id)))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Returns the total spent on the given project per category and overall, and the remaining budget.
+pub fn get_summary(
+    connection: &PgConnection,
+    project: &Project,
+) -> Result<ProjectSummary, ProjectErrorKind> {
+    let amounts = project_expenses::table
+        .inner_join(expenses::table.inner_join(categories::table))
+        .filter(project_expenses::project_id.eq(project.id))
+        .select((categories::id, categories::name, expenses::amount))
+        .load::<(i32, String, Decimal)>(connection)?;
+
+    // Sum the amounts per category, keyed by name so the categories are ordered alphabetically.
+    let mut totals: BTreeMap<(String, i32), Decimal> = BTreeMap::new();
+    for (category_id, category_name, amount) in amounts {
+        *totals
+            .entry((category_name, category_id))
+            .or_insert_with(|| Decimal::new(0, 2)) += amount;
+    }
+
+    let categories: Vec<ProjectCategoryTotal> = totals
+        .into_iter()
+        .map(
+            |((category_name, category_id), total)| ProjectCategoryTotal {
+                category_id,
+                category_name,
+                total,
+            },
+        )
+        .collect();
+    let total = categories
+        .iter()
+        .fold(Decimal::new(0, 2), |sum, c| sum + c.total);
+
+    Ok(ProjectSummary {
+        project: project.clone(),
+        categories,
+        total,
+        remaining: project.budget.map(|budget| budget - total),
+    })
+}
+
+/// Returns the given user's projects without blocking the async executor.
+pub async fn get_projects_async(
+    pool: &ConnectionPool,
+    user: &User,
+) -> Result<Vec<Project>, AsyncError<ProjectErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| get_projects(connection, &user)).await
+}
+
+/// Returns the summary of the project with the given ID without blocking the async executor. An
+/// error is returned if the project does not belong to the given user.
+pub async fn get_summary_async(
+    pool: &ConnectionPool,
+    user: &User,
+    id: i32,
+) -> Result<ProjectSummary, AsyncError<ProjectErrorKind>> {
+    let user_id = user.id;
+    super::run(pool, move |connection| match read(connection, id) {
+        Some(project) if project.user_id == user_id => get_summary(connection, &project),
+        _ => Err(ProjectErrorKind::NotFound(id)),
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_test::*;
+    use crate::{establish_connection, get_database_url};
+    use app::AppConfig;
+    use diesel::result::Error;
+    use std::str::FromStr;
+
+    // Tests creating, reading and deleting projects.
+    #[test]
+    fn test_crud() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            assert!(get_projects(&conn, &user).unwrap().is_empty());
+
+            // The name is required and the budget should be valid.
+            assert_eq!(
+                ProjectErrorKind::MissingData("project name".to_string()),
+                create(&conn, &user, " ", None).unwrap_err()
+            );
+            for budget in &["0.00", "-10.00", "10000000.00"] {
+                let budget = Decimal::from_str(budget).unwrap();
+                assert_eq!(
+                    ProjectErrorKind::InvalidBudget,
+                    create(&conn, &user, "Holiday", Some(&budget)).unwrap_err()
+                );
+            }
+
+            let budget = Decimal::from_str("1500.00").unwrap();
+            let holiday = create(&conn, &user, " Holiday ", Some(&budget)).unwrap();
+            assert_eq!("Holiday", holiday.name);
+            assert_eq!(Some(budget), holiday.budget);
+            assert_eq!(user.id, holiday.user_id);
+            assert_eq!(Some(holiday.clone()), read(&conn, holiday.id));
+
+            // Project names are unique per user.
+            let other_user = create_test_user(&conn, &config);
+            create(&conn, &other_user, "Holiday", None).unwrap();
+
+            let renovation = create(&conn, &user, "Renovation", None).unwrap();
+            assert_eq!(None, renovation.budget);
+            assert_eq!(
+                vec![holiday.clone(), renovation],
+                get_projects(&conn, &user).unwrap()
+            );
+
+            delete(&conn, holiday.id).unwrap();
+            assert_eq!(None, read(&conn, holiday.id));
+            assert_eq!(
+                ProjectErrorKind::NotFound(holiday.id),
+                delete(&conn, holiday.id).unwrap_err()
+            );
+
+            // Check this last since the failed query aborts the transaction.
+            assert_eq!(
+                ProjectErrorKind::ProjectAlreadyExists("Renovation".to_string()),
+                create(&conn, &user, "Renovation", None).unwrap_err()
+            );
+
+            Ok(())
+        });
+    }
+
+    // Tests adding and removing expenses and the project summary.
+    #[test]
+    fn test_summary() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let budget = Decimal::from_str("100.00").unwrap();
+            let trip = create(&conn, &user, "Trip", Some(&budget)).unwrap();
+            let other_trip = create(&conn, &user, "Other trip", None).unwrap();
+            let food = crate::category::create(&conn, &user, "Food", None, None).unwrap();
+            let hotel = crate::category::create(&conn, &user, "Hotel", None, None).unwrap();
+
+            // Initially nothing has been spent.
+            let summary = get_summary(&conn, &trip).unwrap();
+            assert!(summary.categories.is_empty());
+            assert_eq!(Decimal::new(0, 2), summary.total);
+            assert_eq!(Some(budget), summary.remaining);
+
+            let expenses: Vec<Expense> = [(&hotel, "80.00"), (&food, "12.50"), (&food, "7.50")]
+                .iter()
+                .map(|(category, amount)| {
+                    let amount = Decimal::from_str(amount).unwrap();
+                    let expense =
+                        crate::expense::create(&conn, &user, &amount, category, None, None)
+                            .unwrap();
+                    add_expense(&conn, &trip, &expense).unwrap();
+                    expense
+                })
+                .collect();
+
+            // Expenses that are not part of the project are not included.
+            create_test_expense(&conn, &user, &food);
+
+            let expected = vec![
+                ProjectCategoryTotal {
+                    category_id: food.id,
+                    category_name: "Food".to_string(),
+                    total: Decimal::from_str("20.00").unwrap(),
+                },
+                ProjectCategoryTotal {
+                    category_id: hotel.id,
+                    category_name: "Hotel".to_string(),
+                    total: Decimal::from_str("80.00").unwrap(),
+                },
+            ];
+            let summary = get_summary(&conn, &trip).unwrap();
+            assert_eq!(expected, summary.categories);
+            assert_eq!(Decimal::from_str("100.00").unwrap(), summary.total);
+            assert_eq!(Some(Decimal::new(0, 2)), summary.remaining);
+
+            // Moving an expense to another project removes it from the first one.
+            add_expense(&conn, &other_trip, &expenses[0]).unwrap();
+            let summary = get_summary(&conn, &trip).unwrap();
+            assert_eq!(Decimal::from_str("20.00").unwrap(), summary.total);
+            assert_eq!(Some(Decimal::from_str("80.00").unwrap()), summary.remaining);
+            let summary = get_summary(&conn, &other_trip).unwrap();
+            assert_eq!(Decimal::from_str("80.00").unwrap(), summary.total);
+            assert_eq!(None, summary.remaining);
+
+            remove_expense(&conn, &expenses[1]).unwrap();
+            let summary = get_summary(&conn, &trip).unwrap();
+            assert_eq!(Decimal::from_str("7.50").unwrap(), summary.total);
+
+            // Expenses of other users cannot be added.
+            let other_user = create_test_user(&conn, &config);
+            let other_category = create_test_category(&conn, &other_user);
+            let other_expense = create_test_expense(&conn, &other_user, &other_category);
+            assert_eq!(
+                ProjectErrorKind::ExpenseHasWrongUser,
+                add_expense(&conn, &trip, &other_expense).unwrap_err()
+            );
+
+            // Going over budget results in a negative remaining budget.
+            let amount = Decimal::from_str("100.00").unwrap();
+            let expense =
+                crate::expense::create(&conn, &user, &amount, &hotel, None, None).unwrap();
+            add_expense(&conn, &trip, &expense).unwrap();
+            let summary = get_summary(&conn, &trip).unwrap();
+            assert_eq!(Some(Decimal::from_str("-7.50").unwrap()), summary.remaining);
+
+            Ok(())
+        });
+    }
+}
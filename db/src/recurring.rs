@@ -0,0 +1,545 @@
+use super::expense::{Expense, BULK_INSERT_CHUNK_SIZE};
+use super::merchant::Normalizer;
+use super::schema::recurring_expenses::dsl;
+use super::schema::{categories, expenses, recurring_expense_occurrences};
+use super::user::User;
+use super::{AsyncError, ConnectionPool};
+use chrono::{Duration, NaiveDate};
+use diesel::dsl::{exists, not};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// The minimum number of expenses that are needed to recognize a recurring pattern.
+const MIN_OCCURRENCES: usize = 3;
+
+// Expenses that occur more often than this number of days are considered to be regular spending
+// rather than recurring expenses, e.g. buying lunch every day.
+const MIN_INTERVAL_DAYS: i64 = 7;
+
+// The minimum number of days the interval between two expenses may deviate from the typical
+// interval, e.g. to allow for monthly expenses being charged in months with a different length.
+const MIN_INTERVAL_TOLERANCE_DAYS: i64 = 3;
+
+/// An expense that occurs at a regular interval, like a subscription or rent.
+#[derive(Clone, Debug, PartialEq, Queryable, Serialize)]
+pub struct RecurringExpense {
+    pub id: i32,
+    pub user_id: i32,
+    pub category_id: i32,
+    pub description: String,
+    pub amount: Decimal,
+    pub interval_days: i32,
+    // The date on which the next occurrence is expected.
+    pub next_date: NaiveDate,
+}
+
+/// A series of expenses that looks like a recurring expense. The user can accept the suggestion to
+/// create a recurring expense from it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RecurringSuggestion {
+    pub category_id: i32,
+    pub category_name: String,
+    pub description: String,
+    // The amount of the most recent expense in the series.
+    pub amount: Decimal,
+    pub interval_days: i32,
+    pub next_date: NaiveDate,
+    // The IDs of the expenses in the series, in chronological order.
+    pub expense_ids: Vec<i32>,
+}
+
+// Possible errors thrown when handling recurring expenses.
+#[derive(Debug, PartialEq)]
+pub enum RecurringErrorKind {
+    // A recurring expense could not be created due to a database error.
+    CreationFailed(diesel::result::Error),
+    // No recurring pattern was detected for the given category and description.
+    NoSuggestion(i32, String),
+    // The expenses or recurring expenses could not be read due to a database error.
+    ReadFailed(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for RecurringErrorKind {
+    fn from(e: diesel::result::Error) -> Self {
+        RecurringErrorKind::CreationFailed(e)
+    }
+}
+
+impl fmt::Display for RecurringErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecurringErrorKind::CreationFailed(ref err) => {
+                write!(f, "Database error when creating recurring expense: {}", err)
+            }
+            RecurringErrorKind::NoSuggestion(ref category_id, ref description) => write!(
+                f,
+                "No recurring expense detected for '{}' in category {}",
+                description, category_id
+            ),
+            RecurringErrorKind::ReadFailed(ref err) => {
+                write!(f, "Database error when reading recurring expenses: {}", err)
+            }
+        }
+    }
+}
+
+/// Analyzes the expenses of the given user and returns the series of expenses that look like
+/// recurring expenses, ordered by the date of the next expected occurrence.
+///
+/// Expenses are considered to be part of a series if they are from the same merchant and category,
+/// have similar amounts and occur at a regular interval of at least a week. The merchant is derived
+/// from the description using the merchant aliases of the user. Expenses without a description and
+/// expenses that are already linked to a recurring expense are not taken into account, and series
+/// from the same merchant and category as an accepted recurring expense are not suggested again.
+pub fn detect(
+    connection: &PgConnection,
+    user: &User,
+) -> Result<Vec<RecurringSuggestion>, RecurringErrorKind> {
+    let expenses = expenses::table
+        .inner_join(categories::table)
+        .filter(expenses::user_id.eq(user.id))
+        .filter(expenses::description.is_not_null())
+        .filter(not(exists(recurring_expense_occurrences::table.filter(
+            recurring_expense_occurrences::expense_id.eq(expenses::id),
+        ))))
+        .order((expenses::date, expenses::id))
+        .select((expenses::all_columns, categories::name))
+        .load::<(Expense, String)>(connection)
+        .map_err(RecurringErrorKind::ReadFailed)?;
+
+    let normalizer = Normalizer::load(connection, user).map_err(RecurringErrorKind::ReadFailed)?;
+    let recurring_expenses = get_recurring_expenses(connection, user)?;
+
+    Ok(find_series(&expenses, &normalizer)
+        .into_iter()
+        .filter(|s| {
+            !recurring_expenses
+                .iter()
+                .any(|r| is_same_series(r, s.category_id, &s.description))
+        })
+        .collect())
+}
+
+/// Creates a recurring expense from the suggestion that was detected for the given category and
+/// description, and links the expenses of the series to it.
+pub fn accept(
+    connection: &PgConnection,
+    user: &User,
+    category_id: i32,
+    description: &str,
+) -> Result<RecurringExpense, RecurringErrorKind> {
+    // Detect the series in the same transaction, so the linked expenses cannot change in between.
+    connection.transaction::<_, RecurringErrorKind, _>(|| {
+        let suggestion = detect(connection, user)?
+            .into_iter()
+            .find(|s| {
+                s.category_id == category_id
+                    && s.description.to_lowercase() == description.trim().to_lowercase()
+            })
+            .ok_or_else(|| {
+                RecurringErrorKind::NoSuggestion(category_id, description.to_string())
+            })?;
+
+        let recurring_expense = diesel::insert_into(dsl::recurring_expenses)
+            .values((
+                dsl::user_id.eq(user.id),
+                dsl::category_id.eq(suggestion.category_id),
+                dsl::description.eq(&suggestion.description),
+                dsl::amount.eq(suggestion.amount),
+                dsl::interval_days.eq(suggestion.interval_days),
+                dsl::next_date.eq(suggestion.next_date),
+            ))
+            .get_result::<RecurringExpense>(connection)?;
+
+        let occurrences: Vec<_> = suggestion
+            .expense_ids
+            .iter()
+            .map(|expense_id| {
+                (
+                    recurring_expense_occurrences::expense_id.eq(expense_id),
+                    recurring_expense_occurrences::recurring_expense_id.eq(recurring_expense.id),
+                )
+            })
+            .collect();
+        diesel::insert_into(recurring_expense_occurrences::table)
+            .values(occurrences)
+            .execute(connection)?;
+
+        Ok(recurring_expense)
+    })
+}
+
+// Links the given newly created expenses of the given user to the recurring expenses they are an
+// occurrence of, and advances the next date of these recurring expenses. An expense is an
+// occurrence if it is from the same merchant and category and has a similar amount.
+pub(crate) fn link_occurrences(
+    connection: &PgConnection,
+    user: &User,
+    expenses: &[Expense],
+) -> QueryResult<()> {
+    let mut recurring_expenses = dsl::recurring_expenses
+        .filter(dsl::user_id.eq(user.id))
+        .load::<RecurringExpense>(connection)?;
+    if recurring_expenses.is_empty() {
+        return Ok(());
+    }
+    let normalizer = Normalizer::load(connection, user)?;
+
+    let mut occurrences = vec![];
+    let mut advanced = BTreeMap::new();
+    for expense in expenses {
+        let merchant = match &expense.description {
+            Some(description) => normalizer.normalize(description),
+            None => continue,
+        };
+        let recurring_expense = recurring_expenses.iter_mut().find(|r| {
+            is_same_series(r, expense.category_id, &merchant)
+                && is_similar_amount(&expense.amount, &r.amount)
+        });
+        if let Some(recurring_expense) = recurring_expense {
+            occurrences.push((
+                recurring_expense_occurrences::expense_id.eq(expense.id),
+                recurring_expense_occurrences::recurring_expense_id.eq(recurring_expense.id),
+            ));
+
+            // Expenses that are entered late do not move the next date back.
+            let next_date = expense.date + Duration::days(recurring_expense.interval_days.into());
+            if next_date > recurring_expense.next_date {
+                recurring_expense.next_date = next_date;
+                advanced.insert(recurring_expense.id, next_date);
+            }
+        }
+    }
+
+    for chunk in occurrences.chunks(BULK_INSERT_CHUNK_SIZE) {
+        diesel::insert_into(recurring_expense_occurrences::table)
+            .values(chunk)
+            .execute(connection)?;
+    }
+    for (id, next_date) in advanced {
+        diesel::update(dsl::recurring_expenses.filter(dsl::id.eq(id)))
+            .set(dsl::next_date.eq(next_date))
+            .execute(connection)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the recurring expenses of the given user, ordered by the date of the next occurrence.
+pub fn get_recurring_expenses(
+    connection: &PgConnection,
+    user: &User,
+) -> Result<Vec<RecurringExpense>, RecurringErrorKind> {
+    dsl::recurring_expenses
+        .filter(dsl::user_id.eq(user.id))
+        .order((dsl::next_date, dsl::id))
+        .load::<RecurringExpense>(connection)
+        .map_err(RecurringErrorKind::ReadFailed)
+}
+
+/// Returns the expenses that look like recurring expenses without blocking the async executor.
+pub async fn detect_async(
+    pool: &ConnectionPool,
+    user: &User,
+) -> Result<Vec<RecurringSuggestion>, AsyncError<RecurringErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| detect(connection, &user)).await
+}
+
+/// Creates a recurring expense from a suggestion without blocking the async executor.
+pub async fn accept_async(
+    pool: &ConnectionPool,
+    user: &User,
+    category_id: i32,
+    description: &str,
+) -> Result<RecurringExpense, AsyncError<RecurringErrorKind>> {
+    let (user, description) = (user.clone(), description.to_string());
+    super::run(pool, move |connection| {
+        accept(connection, &user, category_id, &description)
+    })
+    .await
+}
+
+/// Returns the recurring expenses of the given user without blocking the async executor.
+pub async fn get_recurring_expenses_async(
+    pool: &ConnectionPool,
+    user: &User,
+) -> Result<Vec<RecurringExpense>, AsyncError<RecurringErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| {
+        get_recurring_expenses(connection, &user)
+    })
+    .await
+}
+
+// Groups the given expenses by category and merchant and returns the groups that occur at a regular
+// interval with similar amounts. The expenses should be ordered by date and each one should be
+// accompanied by the name of its category.
+fn find_series(
+    expenses: &[(Expense, String)],
+    normalizer: &Normalizer,
+) -> Vec<RecurringSuggestion> {
+    let mut groups: BTreeMap<(i32, String), Vec<&(Expense, String)>> = BTreeMap::new();
+    for item in expenses {
+        if let Some(description) = &item.0.description {
+            groups
+                .entry((item.0.category_id, normalizer.normalize(description)))
+                .or_default()
+                .push(item);
+        }
+    }
+
+    let mut suggestions: Vec<RecurringSuggestion> = groups
+        .into_iter()
+        .filter_map(|((_, merchant), group)| to_suggestion(merchant, &group))
+        .collect();
+    suggestions.sort_by_key(|s| s.next_date);
+    suggestions
+}
+
+// Returns a suggestion for the given chronologically ordered group of expenses from the given
+// merchant if they occur at a regular interval and have similar amounts.
+fn to_suggestion(merchant: String, group: &[&(Expense, String)]) -> Option<RecurringSuggestion> {
+    if group.len() < MIN_OCCURRENCES {
+        return None;
+    }
+
+    let mut intervals: Vec<i64> = group
+        .windows(2)
+        .map(|pair| (pair[1].0.date - pair[0].0.date).num_days())
+        .collect();
+    intervals.sort();
+    let interval = median(&intervals);
+    if interval < MIN_INTERVAL_DAYS {
+        return None;
+    }
+    let tolerance = std::cmp::max(MIN_INTERVAL_TOLERANCE_DAYS, interval / 10);
+    if intervals.iter().any(|i| (i - interval).abs() > tolerance) {
+        return None;
+    }
+
+    let mut amounts: Vec<Decimal> = group.iter().map(|(expense, _)| expense.amount).collect();
+    amounts.sort();
+    let amount = median(&amounts);
+    if amounts.iter().any(|a| !is_similar_amount(a, &amount)) {
+        return None;
+    }
+
+    let (last, category_name) = group.last()?;
+    Some(RecurringSuggestion {
+        category_id: last.category_id,
+        category_name: category_name.clone(),
+        description: merchant,
+        amount: last.amount,
+        interval_days: interval as i32,
+        next_date: last.date + Duration::days(interval),
+        expense_ids: group.iter().map(|(expense, _)| expense.id).collect(),
+    })
+}
+
+// Returns whether the given amount does not deviate more than 10% from the typical amount.
+fn is_similar_amount(amount: &Decimal, typical: &Decimal) -> bool {
+    (*amount - *typical).abs() <= *typical / Decimal::from(10)
+}
+
+// Returns whether the given recurring expense is for the given category and merchant.
+fn is_same_series(recurring_expense: &RecurringExpense, category_id: i32, merchant: &str) -> bool {
+    recurring_expense.category_id == category_id
+        && recurring_expense.description.to_lowercase() == merchant.to_lowercase()
+}
+
+// Returns the middle value of the given sorted list.
+fn median<T: Copy>(sorted: &[T]) -> T {
+    sorted[sorted.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_test::*;
+    use crate::{establish_connection, get_database_url};
+    use app::AppConfig;
+    use diesel::result::Error;
+    use std::str::FromStr;
+
+    // Tests find_series().
+    #[test]
+    fn test_find_series() {
+        let expenses = vec![
+            // A monthly subscription with a small price change. The descriptions are normalized.
+            expense(1, 1, "Streaming", "9.99", "2020-01-31"),
+            // A weekly expense.
+            expense(2, 2, "Cleaner", "40.00", "2020-02-01"),
+            expense(3, 2, "Cleaner", "40.00", "2020-02-08"),
+            expense(4, 1, "CARD 1234 STREAMING*AB12", "9.99", "2020-02-29"),
+            expense(5, 2, "Cleaner", "40.00", "2020-02-15"),
+            // Daily expenses are not considered to be recurring.
+            expense(6, 3, "Lunch", "8.50", "2020-03-01"),
+            expense(7, 3, "Lunch", "8.50", "2020-03-02"),
+            expense(8, 3, "Lunch", "8.50", "2020-03-03"),
+            expense(9, 1, "Streaming", "10.49", "2020-03-31"),
+            // Expenses with the same description in a different category are not part of the
+            // series.
+            expense(10, 3, "Streaming", "9.99", "2020-04-02"),
+            // Expenses with irregular intervals are not recurring.
+            expense(11, 4, "Fuel", "50.00", "2020-01-01"),
+            expense(12, 4, "Fuel", "50.00", "2020-01-20"),
+            expense(13, 4, "Fuel", "50.00", "2020-03-01"),
+            // Expenses with strongly varying amounts are not recurring.
+            expense(14, 5, "Groceries", "20.00", "2020-01-04"),
+            expense(15, 5, "Groceries", "95.00", "2020-01-11"),
+            expense(16, 5, "Groceries", "43.00", "2020-01-18"),
+        ];
+
+        let expected = vec![
+            RecurringSuggestion {
+                category_id: 2,
+                category_name: "Category 2".to_string(),
+                description: "Cleaner".to_string(),
+                amount: Decimal::from_str("40.00").unwrap(),
+                interval_days: 7,
+                next_date: NaiveDate::from_ymd(2020, 2, 22),
+                expense_ids: vec![2, 3, 5],
+            },
+            RecurringSuggestion {
+                category_id: 1,
+                category_name: "Category 1".to_string(),
+                description: "Streaming".to_string(),
+                amount: Decimal::from_str("10.49").unwrap(),
+                interval_days: 31,
+                next_date: NaiveDate::from_ymd(2020, 5, 1),
+                expense_ids: vec![1, 4, 9],
+            },
+        ];
+        assert_eq!(expected, find_series(&expenses, &Normalizer::default()));
+    }
+
+    // Tests detect() and accept().
+    #[test]
+    fn test_detect_and_accept() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = create_test_category(&conn, &user);
+
+            // Initially there is nothing to detect.
+            assert!(detect(&conn, &user).unwrap().is_empty());
+            assert_eq!(
+                RecurringErrorKind::NoSuggestion(cat.id, "Rent".to_string()),
+                accept(&conn, &user, cat.id, "Rent").unwrap_err()
+            );
+
+            let amount = Decimal::from_str("850.00").unwrap();
+            let mut expense_ids = vec![];
+            for date in &["2020-03-01", "2020-04-01", "2020-05-01"] {
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+                let expense =
+                    crate::expense::create(&conn, &user, &amount, &cat, Some("Rent"), Some(&date))
+                        .unwrap();
+                expense_ids.push(expense.id);
+            }
+
+            let suggestions = detect(&conn, &user).unwrap();
+            assert_eq!(1, suggestions.len());
+            assert_eq!(expense_ids, suggestions[0].expense_ids);
+
+            // The suggestions of other users are not returned.
+            let other_user = create_test_user(&conn, &config);
+            assert!(detect(&conn, &other_user).unwrap().is_empty());
+
+            let recurring_expense = accept(&conn, &user, cat.id, "rent").unwrap();
+            assert_eq!(user.id, recurring_expense.user_id);
+            assert_eq!(cat.id, recurring_expense.category_id);
+            assert_eq!("Rent", recurring_expense.description);
+            assert_eq!(amount, recurring_expense.amount);
+            assert_eq!(31, recurring_expense.interval_days);
+            assert_eq!(NaiveDate::from_ymd(2020, 6, 1), recurring_expense.next_date);
+            assert_eq!(
+                vec![recurring_expense],
+                get_recurring_expenses(&conn, &user).unwrap()
+            );
+
+            // Once the series is linked to a recurring expense it is no longer suggested.
+            assert!(detect(&conn, &user).unwrap().is_empty());
+
+            // New occurrences are linked to the recurring expense and advance the next date.
+            let date = NaiveDate::from_ymd(2020, 6, 1);
+            let expense =
+                crate::expense::create(&conn, &user, &amount, &cat, Some("RENT "), Some(&date))
+                    .unwrap();
+            let recurring_expense = &get_recurring_expenses(&conn, &user).unwrap()[0];
+            assert_eq!(NaiveDate::from_ymd(2020, 7, 2), recurring_expense.next_date);
+            assert_eq!(
+                Ok(recurring_expense.id),
+                get_recurring_expense_id(&conn, expense.id)
+            );
+
+            // Occurrences that are entered late do not move the next date back.
+            let date = NaiveDate::from_ymd(2020, 2, 1);
+            crate::expense::create(&conn, &user, &amount, &cat, Some("Rent"), Some(&date)).unwrap();
+            let recurring_expense = &get_recurring_expenses(&conn, &user).unwrap()[0];
+            assert_eq!(NaiveDate::from_ymd(2020, 7, 2), recurring_expense.next_date);
+
+            // Expenses from the same merchant with a very different amount are not occurrences.
+            // They are not suggested as a new recurring expense either.
+            let other_amount = Decimal::from_str("1500.00").unwrap();
+            for date in &["2020-07-15", "2020-08-15", "2020-09-15"] {
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+                let expense = crate::expense::create(
+                    &conn,
+                    &user,
+                    &other_amount,
+                    &cat,
+                    Some("Rent"),
+                    Some(&date),
+                )
+                .unwrap();
+                assert_eq!(
+                    Err(diesel::result::Error::NotFound),
+                    get_recurring_expense_id(&conn, expense.id)
+                );
+            }
+            assert!(detect(&conn, &user).unwrap().is_empty());
+            assert_eq!(
+                RecurringErrorKind::NoSuggestion(cat.id, "Rent".to_string()),
+                accept(&conn, &user, cat.id, "Rent").unwrap_err()
+            );
+
+            Ok(())
+        });
+    }
+
+    // Returns the ID of the recurring expense the given expense is linked to.
+    fn get_recurring_expense_id(conn: &PgConnection, expense_id: i32) -> QueryResult<i32> {
+        recurring_expense_occurrences::table
+            .filter(recurring_expense_occurrences::expense_id.eq(expense_id))
+            .select(recurring_expense_occurrences::recurring_expense_id)
+            .first(conn)
+    }
+
+    // Returns an expense with the given data, accompanied by the name of its category.
+    fn expense(
+        id: i32,
+        category_id: i32,
+        description: &str,
+        amount: &str,
+        date: &str,
+    ) -> (Expense, String) {
+        let expense = Expense {
+            id,
+            amount: Decimal::from_str(amount).unwrap(),
+            description: Some(description.to_string()),
+            category_id,
+            user_id: 1,
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            import_reference: None,
+        };
+        (expense, format!("Category {}", category_id))
+    }
+}
@@ -0,0 +1,418 @@
+use super::expense::Expense;
+use super::schema::expenses;
+use super::schema::reimbursements::dsl;
+use super::user::User;
+use chrono::NaiveDate;
+use diesel::pg::upsert::excluded;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// An expense that was paid personally but will be paid back by someone else, e.g. a work expense
+/// that is reimbursed by the employer.
+#[derive(Clone, Debug, PartialEq, Queryable, Serialize)]
+pub struct Reimbursement {
+    pub expense_id: i32,
+    pub user_id: i32,
+    // The person or organization that will pay back the expense.
+    pub payer: String,
+    pub status: String,
+    pub reimbursed_date: Option<NaiveDate>,
+}
+
+/// The statuses of a reimbursement.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReimbursementStatus {
+    /// The expense has not been paid back yet.
+    Outstanding,
+    /// The expense has been paid back.
+    Reimbursed,
+}
+
+impl ReimbursementStatus {
+    /// Returns the value that is stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReimbursementStatus::Outstanding => "outstanding",
+            ReimbursementStatus::Reimbursed => "reimbursed",
+        }
+    }
+}
+
+/// The expenses that still need to be paid back by a payer.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct OutstandingReimbursements {
+    pub payer: String,
+    pub total: Decimal,
+    // The outstanding expenses, ordered by date.
+    pub expenses: Vec<Expense>,
+}
+
+// Possible errors thrown when handling reimbursements.
+#[derive(Debug, PartialEq)]
+pub enum ReimbursementErrorKind {
+    // A database error occurred.
+    DatabaseError(diesel::result::Error),
+    // Some required data is missing.
+    MissingData(String),
+    // An incoming payment does not match any outstanding expenses of the payer.
+    NoMatchingExpenses(String, Decimal),
+    // The expense with the given ID is not marked as reimbursable.
+    NotReimbursable(i32),
+}
+
+impl fmt::Display for ReimbursementErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReimbursementErrorKind::DatabaseError(ref err) => write!(f, "Database error: {}", err),
+            ReimbursementErrorKind::MissingData(ref err) => {
+                write!(f, "Missing data for field: {}", err)
+            }
+            ReimbursementErrorKind::NoMatchingExpenses(ref payer, ref amount) => write!(
+                f,
+                "No outstanding expenses of {} match the amount of {}",
+                payer, amount
+            ),
+            ReimbursementErrorKind::NotReimbursable(ref id) => {
+                write!(f, "Expense {} is not marked as reimbursable", id)
+            }
+        }
+    }
+}
+
+impl From<diesel::result::Error> for ReimbursementErrorKind {
+    fn from(e: diesel::result::Error) -> Self {
+        ReimbursementErrorKind::DatabaseError(e)
+    }
+}
+
+/// Marks the given expense as reimbursable by the given payer. If the expense was already marked as
+/// reimbursable, the payer is updated.
+pub fn mark_reimbursable(
+    connection: &PgConnection,
+    expense: &Expense,
+    payer: &str,
+) -> Result<Reimbursement, ReimbursementErrorKind> {
+    let payer = payer.trim();
+    if payer.is_empty() {
+        return Err(ReimbursementErrorKind::MissingData("payer".to_string()));
+    }
+
+    Ok(diesel::insert_into(dsl::reimbursements)
+        .values((
+            dsl::expense_id.eq(expense.id),
+            dsl::user_id.eq(expense.user_id),
+            dsl::payer.eq(payer),
+            dsl::status.eq(ReimbursementStatus::Outstanding.as_str()),
+        ))
+        .on_conflict(dsl::expense_id)
+        .do_update()
+        .set(dsl::payer.eq(excluded(dsl::payer)))
+        .get_result(connection)?)
+}
+
+/// Removes the reimbursable flag from the given expense.
+pub fn unmark_reimbursable(
+    connection: &PgConnection,
+    expense: &Expense,
+) -> Result<(), ReimbursementErrorKind> {
+    let count = diesel::delete(dsl::reimbursements.filter(dsl::expense_id.eq(expense.id)))
+        .execute(connection)?;
+
+    match count {
+        0 => Err(ReimbursementErrorKind::NotReimbursable(expense.id)),
+        _ => Ok(()),
+    }
+}
+
+/// Retrieves the reimbursement of the given expense, if it is reimbursable.
+pub fn read(connection: &PgConnection, expense: &Expense) -> Option<Reimbursement> {
+    dsl::reimbursements
+        .find(expense.id)
+        .first::<Reimbursement>(connection)
+        .ok()
+}
+
+/// Marks the given expense as reimbursed on the given date.
+pub fn mark_reimbursed(
+    connection: &PgConnection,
+    expense: &Expense,
+    date: &NaiveDate,
+) -> Result<Reimbursement, ReimbursementErrorKind> {
+    let result = diesel::update(dsl::reimbursements.filter(dsl::expense_id.eq(expense.id)))
+        .set((
+            dsl::status.eq(ReimbursementStatus::Reimbursed.as_str()),
+            dsl::reimbursed_date.eq(date),
+        ))
+        .get_result(connection);
+
+    match result {
+        Ok(reimbursement) => Ok(reimbursement),
+        Err(diesel::result::Error::NotFound) => {
+            Err(ReimbursementErrorKind::NotReimbursable(expense.id))
+        }
+        Err(e) => Err(ReimbursementErrorKind::DatabaseError(e)),
+    }
+}
+
+/// Returns the expenses of the given user that have not been paid back yet, grouped by payer. Payers
+/// that only differ in case or whitespace are grouped together.
+pub fn get_outstanding(
+    connection: &PgConnection,
+    user: &User,
+) -> Result<Vec<OutstandingReimbursements>, ReimbursementErrorKind> {
+    let outstanding = load_outstanding(connection, user)?;
+
+    let mut payers: BTreeMap<String, OutstandingReimbursements> = BTreeMap::new();
+    for (payer, expense) in outstanding {
+        let entry =
+            payers
+                .entry(normalize_payer(&payer))
+                .or_insert_with(|| OutstandingReimbursements {
+                    payer,
+                    total: Decimal::new(0, 2),
+                    expenses: vec![],
+                });
+        entry.total += expense.amount;
+        entry.expenses.push(expense);
+    }
+
+    Ok(payers.into_values().collect())
+}
+
+/// Records an incoming payment from the given payer and marks the matching outstanding expenses as
+/// reimbursed. Returns the expenses that were marked.
+///
+/// The payment matches the oldest outstanding expense with exactly the same amount. If there is no
+/// such expense but the payment equals the total of all outstanding expenses of the payer, they are
+/// all marked as reimbursed. Differences in case and whitespace in the name of the payer are
+/// ignored.
+pub fn record_payment(
+    connection: &PgConnection,
+    user: &User,
+    payer: &str,
+    amount: &Decimal,
+    date: &NaiveDate,
+) -> Result<Vec<Expense>, ReimbursementErrorKind> {
+    let payer = payer.trim();
+    let normalized_payer = normalize_payer(payer);
+
+    // Read the outstanding expenses in the same transaction, so they cannot be reimbursed in
+    // between.
+    connection.transaction::<_, ReimbursementErrorKind, _>(|| {
+        let outstanding: Vec<Expense> = load_outstanding(connection, user)?
+            .into_iter()
+            .filter(|(p, _)| normalize_payer(p) == normalized_payer)
+            .map(|(_, expense)| expense)
+            .collect();
+
+        let matching = match outstanding.iter().find(|e| e.amount == *amount) {
+            Some(expense) => vec![expense.clone()],
+            None => {
+                let total = outstanding
+                    .iter()
+                    .fold(Decimal::new(0, 2), |sum, e| sum + e.amount);
+                if outstanding.is_empty() || total != *amount {
+                    return Err(ReimbursementErrorKind::NoMatchingExpenses(
+                        payer.to_string(),
+                        *amount,
+                    ));
+                }
+                outstanding
+            }
+        };
+
+        for expense in &matching {
+            mark_reimbursed(connection, expense, date)?;
+        }
+        Ok(matching)
+    })
+}
+
+// Loads the outstanding expenses of the given user together with their payer, ordered by payer and
+// date.
+fn load_outstanding(connection: &PgConnection, user: &User) -> QueryResult<Vec<(String, Expense)>> {
+    dsl::reimbursements
+        .inner_join(expenses::table)
+        .filter(dsl::user_id.eq(user.id))
+        .filter(dsl::status.eq(ReimbursementStatus::Outstanding.as_str()))
+        .select((dsl::payer, expenses::all_columns))
+        .order((dsl::payer, expenses::date, expenses::id))
+        .for_update()
+        .load::<(String, Expense)>(connection)
+}
+
+// Returns the name of the payer in lower case with consecutive whitespace collapsed, so that names
+// that were entered slightly differently can be compared.
+fn normalize_payer(payer: &str) -> String {
+    payer
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_test::*;
+    use crate::{establish_connection, get_database_url};
+    use app::AppConfig;
+    use diesel::result::Error;
+    use std::str::FromStr;
+
+    // Tests marking expenses as reimbursable and the outstanding reimbursements.
+    #[test]
+    fn test_outstanding() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = create_test_category(&conn, &user);
+            let expenses: Vec<Expense> = (0..4)
+                .map(|_| create_test_expense(&conn, &user, &cat))
+                .collect();
+            assert!(get_outstanding(&conn, &user).unwrap().is_empty());
+
+            // The payer is required.
+            assert_eq!(
+                ReimbursementErrorKind::MissingData("payer".to_string()),
+                mark_reimbursable(&conn, &expenses[0], " ").unwrap_err()
+            );
+
+            let reimbursement = mark_reimbursable(&conn, &expenses[0], " Acme ").unwrap();
+            assert_eq!("Acme", reimbursement.payer);
+            assert_eq!(
+                ReimbursementStatus::Outstanding.as_str(),
+                reimbursement.status
+            );
+            assert_eq!(None, reimbursement.reimbursed_date);
+            assert_eq!(Some(reimbursement), read(&conn, &expenses[0]));
+            assert_eq!(None, read(&conn, &expenses[3]));
+
+            // Marking an expense again updates the payer.
+            mark_reimbursable(&conn, &expenses[1], "Acme").unwrap();
+            mark_reimbursable(&conn, &expenses[1], "Globex").unwrap();
+            mark_reimbursable(&conn, &expenses[2], "Acme").unwrap();
+
+            let outstanding = get_outstanding(&conn, &user).unwrap();
+            assert_eq!(2, outstanding.len());
+            assert_eq!("Acme", outstanding[0].payer);
+            assert_eq!(
+                vec![expenses[0].clone(), expenses[2].clone()],
+                outstanding[0].expenses
+            );
+            assert_eq!(
+                expenses[0].amount + expenses[2].amount,
+                outstanding[0].total
+            );
+            assert_eq!("Globex", outstanding[1].payer);
+            assert_eq!(vec![expenses[1].clone()], outstanding[1].expenses);
+
+            // Reimbursed and unmarked expenses are no longer outstanding.
+            let date = NaiveDate::from_ymd(2020, 6, 15);
+            let reimbursement = mark_reimbursed(&conn, &expenses[0], &date).unwrap();
+            assert_eq!(
+                ReimbursementStatus::Reimbursed.as_str(),
+                reimbursement.status
+            );
+            assert_eq!(Some(date), reimbursement.reimbursed_date);
+            unmark_reimbursable(&conn, &expenses[1]).unwrap();
+            let outstanding = get_outstanding(&conn, &user).unwrap();
+            assert_eq!(1, outstanding.len());
+            assert_eq!(vec![expenses[2].clone()], outstanding[0].expenses);
+
+            // Expenses that are not reimbursable cannot be marked as reimbursed.
+            assert_eq!(
+                ReimbursementErrorKind::NotReimbursable(expenses[3].id),
+                mark_reimbursed(&conn, &expenses[3], &date).unwrap_err()
+            );
+            assert_eq!(
+                ReimbursementErrorKind::NotReimbursable(expenses[3].id),
+                unmark_reimbursable(&conn, &expenses[3]).unwrap_err()
+            );
+
+            // The reimbursements of other users are not returned.
+            let other_user = create_test_user(&conn, &config);
+            assert!(get_outstanding(&conn, &other_user).unwrap().is_empty());
+
+            Ok(())
+        });
+    }
+
+    // Tests normalize_payer().
+    #[test]
+    fn test_normalize_payer() {
+        assert_eq!("acme", normalize_payer("Acme"));
+        assert_eq!("acme corp", normalize_payer("  ACME \t Corp "));
+        assert_eq!("", normalize_payer(" "));
+    }
+
+    // Tests record_payment().
+    #[test]
+    fn test_record_payment() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = create_test_category(&conn, &user);
+            let date = NaiveDate::from_ymd(2020, 6, 15);
+            let expenses: Vec<Expense> = [
+                ("25.00", "2020-06-01", "Acme"),
+                ("40.00", "2020-06-02", "Acme"),
+                ("25.00", "2020-06-03", "acme"),
+                ("40.00", "2020-06-04", "Globex"),
+            ]
+            .iter()
+            .map(|(amount, date, payer)| {
+                let amount = Decimal::from_str(amount).unwrap();
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+                let expense =
+                    crate::expense::create(&conn, &user, &amount, &cat, None, Some(&date)).unwrap();
+                mark_reimbursable(&conn, &expense, payer).unwrap();
+                expense
+            })
+            .collect();
+
+            // A payment that doesn't match anything is refused.
+            let amount = Decimal::from_str("30.00").unwrap();
+            assert_eq!(
+                ReimbursementErrorKind::NoMatchingExpenses("Acme".to_string(), amount),
+                record_payment(&conn, &user, "Acme", &amount, &date).unwrap_err()
+            );
+
+            // A payment matching a single expense marks the oldest one.
+            let amount = Decimal::from_str("25.00").unwrap();
+            let reimbursed = record_payment(&conn, &user, "Acme", &amount, &date).unwrap();
+            assert_eq!(vec![expenses[0].clone()], reimbursed);
+            let reimbursement = read(&conn, &expenses[0]).unwrap();
+            assert_eq!(
+                ReimbursementStatus::Reimbursed.as_str(),
+                reimbursement.status
+            );
+            assert_eq!(Some(date), reimbursement.reimbursed_date);
+
+            // A payment matching the total marks all outstanding expenses of the payer. Differences
+            // in case and whitespace are ignored.
+            let amount = Decimal::from_str("65.00").unwrap();
+            let reimbursed = record_payment(&conn, &user, " ACME ", &amount, &date).unwrap();
+            assert_eq!(vec![expenses[1].clone(), expenses[2].clone()], reimbursed);
+
+            // Nothing is outstanding for the payer any more.
+            assert_eq!(
+                ReimbursementErrorKind::NoMatchingExpenses("Acme".to_string(), amount),
+                record_payment(&conn, &user, "Acme", &amount, &date).unwrap_err()
+            );
+            let outstanding = get_outstanding(&conn, &user).unwrap();
+            assert_eq!(1, outstanding.len());
+            assert_eq!("Globex", outstanding[0].payer);
+
+            Ok(())
+        });
+    }
+}
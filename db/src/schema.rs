@@ -17,6 +17,34 @@ table! {
     }
 }
 
+table! {
+    category_tax_defaults (category_id) {
+        category_id -> Int4,
+        tax_rate -> Numeric,
+        deductible -> Bool,
+    }
+}
+
+table! {
+    expense_alerts (id) {
+        id -> Int4,
+        expense_id -> Int4,
+        user_id -> Int4,
+        reason -> Varchar,
+        status -> Varchar,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    expense_taxes (expense_id) {
+        expense_id -> Int4,
+        tax_rate -> Numeric,
+        tax_amount -> Numeric,
+        deductible -> Bool,
+    }
+}
+
 table! {
     expenses (id) {
         id -> Int4,
@@ -25,6 +53,71 @@ table! {
         category_id -> Int4,
         user_id -> Int4,
         date -> Date,
+        import_reference -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    merchant_aliases (id) {
+        id -> Int4,
+        user_id -> Int4,
+        pattern -> Varchar,
+        name -> Varchar,
+    }
+}
+
+table! {
+    monthly_category_totals (category_id, month) {
+        category_id -> Int4,
+        month -> Date,
+        user_id -> Int4,
+        total -> Numeric,
+        expense_count -> Int4,
+    }
+}
+
+table! {
+    project_expenses (expense_id) {
+        expense_id -> Int4,
+        project_id -> Int4,
+    }
+}
+
+table! {
+    projects (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        budget -> Nullable<Numeric>,
+    }
+}
+
+table! {
+    recurring_expense_occurrences (expense_id) {
+        expense_id -> Int4,
+        recurring_expense_id -> Int4,
+    }
+}
+
+table! {
+    recurring_expenses (id) {
+        id -> Int4,
+        user_id -> Int4,
+        category_id -> Int4,
+        description -> Varchar,
+        amount -> Numeric,
+        interval_days -> Int4,
+        next_date -> Date,
+    }
+}
+
+table! {
+    reimbursements (expense_id) {
+        expense_id -> Int4,
+        user_id -> Int4,
+        payer -> Varchar,
+        status -> Varchar,
+        reimbursed_date -> Nullable<Date>,
     }
 }
 
@@ -40,7 +133,38 @@ table! {
 
 joinable!(activation_codes -> users (id));
 joinable!(categories -> users (user_id));
+joinable!(category_tax_defaults -> categories (category_id));
+joinable!(expense_alerts -> expenses (expense_id));
+joinable!(expense_alerts -> users (user_id));
+joinable!(expense_taxes -> expenses (expense_id));
 joinable!(expenses -> categories (category_id));
 joinable!(expenses -> users (user_id));
+joinable!(merchant_aliases -> users (user_id));
+joinable!(monthly_category_totals -> categories (category_id));
+joinable!(monthly_category_totals -> users (user_id));
+joinable!(project_expenses -> expenses (expense_id));
+joinable!(project_expenses -> projects (project_id));
+joinable!(projects -> users (user_id));
+joinable!(recurring_expense_occurrences -> expenses (expense_id));
+joinable!(recurring_expense_occurrences -> recurring_expenses (recurring_expense_id));
+joinable!(recurring_expenses -> categories (category_id));
+joinable!(recurring_expenses -> users (user_id));
+joinable!(reimbursements -> expenses (expense_id));
+joinable!(reimbursements -> users (user_id));
 
-allow_tables_to_appear_in_same_query!(activation_codes, categories, expenses, users,);
+allow_tables_to_appear_in_same_query!(
+    activation_codes,
+    categories,
+    category_tax_defaults,
+    expense_alerts,
+    expense_taxes,
+    expenses,
+    merchant_aliases,
+    monthly_category_totals,
+    project_expenses,
+    projects,
+    recurring_expense_occurrences,
+    recurring_expenses,
+    reimbursements,
+    users,
+);
@@ -0,0 +1,593 @@
+use super::category::Category;
+use super::expense::Expense;
+use super::schema::{categories, category_tax_defaults, expense_taxes, expenses};
+use super::user::User;
+use super::{AsyncError, ConnectionPool};
+use chrono::NaiveDate;
+use diesel::pg::upsert::excluded;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fmt;
+
+/// The tax information of an expense.
+#[derive(Clone, Debug, PartialEq, Queryable, Serialize)]
+pub struct ExpenseTax {
+    pub expense_id: i32,
+    // The tax rate as a percentage, e.g. 21.00.
+    pub tax_rate: Decimal,
+    // The part of the amount of the expense that consists of tax.
+    pub tax_amount: Decimal,
+    // Whether the tax can be deducted, e.g. VAT on business expenses.
+    pub deductible: bool,
+}
+
+/// The tax information that is applied to new expenses in a category.
+#[derive(Clone, Debug, PartialEq, Queryable, Serialize)]
+pub struct CategoryTaxDefault {
+    pub category_id: i32,
+    pub tax_rate: Decimal,
+    pub deductible: bool,
+}
+
+/// A line in the yearly tax report.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TaxReportLine {
+    pub expense: Expense,
+    pub category_name: String,
+    pub tax: ExpenseTax,
+}
+
+/// An overview of the taxes paid on expenses during a year.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TaxReport {
+    pub year: i32,
+    // The expenses with tax information, ordered by date.
+    pub lines: Vec<TaxReportLine>,
+    pub total_amount: Decimal,
+    pub total_tax: Decimal,
+    pub deductible_tax: Decimal,
+}
+
+impl TaxReport {
+    /// Returns the report in CSV format, with a header line and a line for every expense.
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("Date,Category,Description,Amount,Tax rate,Tax amount,Deductible\r\n");
+        for line in &self.lines {
+            let fields = [
+                line.expense.date.format("%Y-%m-%d").to_string(),
+                line.category_name.clone(),
+                line.expense.description.clone().unwrap_or_default(),
+                line.expense.amount.to_string(),
+                line.tax.tax_rate.to_string(),
+                line.tax.tax_amount.to_string(),
+                if line.tax.deductible { "yes" } else { "no" }.to_string(),
+            ];
+            let fields: Vec<String> = fields.iter().map(|f| escape_csv_field(f)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+}
+
+// Possible errors thrown when handling taxes.
+#[derive(Debug, PartialEq)]
+pub enum TaxErrorKind {
+    // A database error occurred.
+    DatabaseError(diesel::result::Error),
+    // The tax amount should not be negative and not exceed the amount of the expense.
+    InvalidTaxAmount,
+    // The year is outside of the range of supported dates.
+    InvalidYear(i32),
+    // The tax rate should be a percentage between 0 and 100.
+    InvalidTaxRate,
+    // The expense with the given ID has no tax information.
+    NotFound(i32),
+}
+
+impl fmt::Display for TaxErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TaxErrorKind::DatabaseError(ref err) => write!(f, "Database error: {}", err),
+            TaxErrorKind::InvalidTaxAmount => write!(
+                f,
+                "Tax amount should be between 0.00 and the amount of the expense"
+            ),
+            TaxErrorKind::InvalidTaxRate => write!(f, "Tax rate should be between 0.00 and 99.99"),
+            TaxErrorKind::InvalidYear(ref year) => write!(f, "Invalid year: {}", year),
+            TaxErrorKind::NotFound(ref id) => write!(f, "Expense {} has no tax information", id),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for TaxErrorKind {
+    fn from(e: diesel::result::Error) -> Self {
+        TaxErrorKind::DatabaseError(e)
+    }
+}
+
+/// Sets the tax information of the given expense. If no tax amount is given, it is calculated
+/// from the tax rate, assuming the amount of the expense includes the tax.
+pub fn set_expense_tax(
+    connection: &PgConnection,
+    expense: &Expense,
+    tax_rate: &Decimal,
+    tax_amount: Option<&Decimal>,
+    deductible: bool,
+) -> Result<ExpenseTax, TaxErrorKind> {
+    validate_tax_rate(tax_rate)?;
+    let tax_amount = match tax_amount {
+        Some(tax_amount) => *tax_amount,
+        None => calculate_tax(&expense.amount, tax_rate),
+    };
+    if tax_amount < Decimal::new(0, 2) || tax_amount > expense.amount {
+        return Err(TaxErrorKind::InvalidTaxAmount);
+    }
+
+    Ok(diesel::insert_into(expense_taxes::table)
+        .values((
+            expense_taxes::expense_id.eq(expense.id),
+            expense_taxes::tax_rate.eq(tax_rate),
+            expense_taxes::tax_amount.eq(tax_amount),
+            expense_taxes::deductible.eq(deductible),
+        ))
+        .on_conflict(expense_taxes::expense_id)
+        .do_update()
+        .set((
+            expense_taxes::tax_rate.eq(excluded(expense_taxes::tax_rate)),
+            expense_taxes::tax_amount.eq(excluded(expense_taxes::tax_amount)),
+            expense_taxes::deductible.eq(excluded(expense_taxes::deductible)),
+        ))
+        .get_result(connection)?)
+}
+
+/// Retrieves the tax information of the given expense, if it has any.
+pub fn read(connection: &PgConnection, expense: &Expense) -> Option<ExpenseTax> {
+    expense_taxes::table
+        .find(expense.id)
+        .first::<ExpenseTax>(connection)
+        .ok()
+}
+
+/// Removes the tax information from the given expense.
+pub fn remove_expense_tax(
+    connection: &PgConnection,
+    expense: &Expense,
+) -> Result<(), TaxErrorKind> {
+    let count =
+        diesel::delete(expense_taxes::table.filter(expense_taxes::expense_id.eq(expense.id)))
+            .execute(connection)?;
+
+    match count {
+        0 => Err(TaxErrorKind::NotFound(expense.id)),
+        _ => Ok(()),
+    }
+}
+
+/// Sets the tax information that is applied to new expenses in the given category.
+pub fn set_category_default(
+    connection: &PgConnection,
+    category: &Category,
+    tax_rate: &Decimal,
+    deductible: bool,
+) -> Result<CategoryTaxDefault, TaxErrorKind> {
+    validate_tax_rate(tax_rate)?;
+
+    Ok(diesel::insert_into(category_tax_defaults::table)
+        .values((
+            category_tax_defaults::category_id.eq(category.id),
+            category_tax_defaults::tax_rate.eq(tax_rate),
+            category_tax_defaults::deductible.eq(deductible),
+        ))
+        .on_conflict(category_tax_defaults::category_id)
+        .do_update()
+        .set((
+            category_tax_defaults::tax_rate.eq(excluded(category_tax_defaults::tax_rate)),
+            category_tax_defaults::deductible.eq(excluded(category_tax_defaults::deductible)),
+        ))
+        .get_result(connection)?)
+}
+
+/// Retrieves the tax information that is applied to new expenses in the given category.
+pub fn get_category_default(
+    connection: &PgConnection,
+    category: &Category,
+) -> Option<CategoryTaxDefault> {
+    category_tax_defaults::table
+        .find(category.id)
+        .first::<CategoryTaxDefault>(connection)
+        .ok()
+}
+
+/// Removes the tax information that is applied to new expenses in the given category. Existing
+/// expenses keep their tax information.
+pub fn remove_category_default(
+    connection: &PgConnection,
+    category: &Category,
+) -> Result<(), TaxErrorKind> {
+    diesel::delete(
+        category_tax_defaults::table.filter(category_tax_defaults::category_id.eq(category.id)),
+    )
+    .execute(connection)?;
+    Ok(())
+}
+
+/// Returns the tax report of the given user for the given year.
+pub fn get_yearly_report(
+    connection: &PgConnection,
+    user: &User,
+    year: i32,
+) -> Result<TaxReport, TaxErrorKind> {
+    let first_day = NaiveDate::from_ymd_opt(year, 1, 1).ok_or(TaxErrorKind::InvalidYear(year))?;
+    let last_day = NaiveDate::from_ymd_opt(year, 12, 31).ok_or(TaxErrorKind::InvalidYear(year))?;
+    let lines: Vec<TaxReportLine> = expense_taxes::table
+        .inner_join(expenses::table.inner_join(categories::table))
+        .filter(expenses::user_id.eq(user.id))
+        .filter(expenses::date.ge(first_day))
+        .filter(expenses::date.le(last_day))
+        .order((expenses::date, expenses::id))
+        .select((
+            expenses::all_columns,
+            categories::name,
+            expense_taxes::all_columns,
+        ))
+        .load::<(Expense, String, ExpenseTax)>(connection)?
+        .into_iter()
+        .map(|(expense, category_name, tax)| TaxReportLine {
+            expense,
+            category_name,
+            tax,
+        })
+        .collect();
+
+    let zero = Decimal::new(0, 2);
+    let total_amount = lines.iter().fold(zero, |sum, l| sum + l.expense.amount);
+    let total_tax = lines.iter().fold(zero, |sum, l| sum + l.tax.tax_amount);
+    let deductible_tax = lines
+        .iter()
+        .filter(|l| l.tax.deductible)
+        .fold(zero, |sum, l| sum + l.tax.tax_amount);
+
+    Ok(TaxReport {
+        year,
+        lines,
+        total_amount,
+        total_tax,
+        deductible_tax,
+    })
+}
+
+/// Returns the tax report of the given user for the given year without blocking the async executor.
+pub async fn get_yearly_report_async(
+    pool: &ConnectionPool,
+    user: &User,
+    year: i32,
+) -> Result<TaxReport, AsyncError<TaxErrorKind>> {
+    let user = user.clone();
+    super::run(pool, move |connection| {
+        get_yearly_report(connection, &user, year)
+    })
+    .await
+}
+
+// Applies the tax defaults of their categories to the given newly created expenses.
+pub(crate) fn apply_category_defaults(
+    connection: &PgConnection,
+    expenses: &[Expense],
+) -> QueryResult<()> {
+    let mut category_ids: Vec<i32> = expenses.iter().map(|e| e.category_id).collect();
+    category_ids.sort_unstable();
+    category_ids.dedup();
+    let defaults = category_tax_defaults::table
+        .filter(category_tax_defaults::category_id.eq_any(category_ids))
+        .load::<CategoryTaxDefault>(connection)?;
+    if defaults.is_empty() {
+        return Ok(());
+    }
+
+    for chunk in expenses.chunks(super::expense::BULK_INSERT_CHUNK_SIZE) {
+        let values: Vec<_> = chunk
+            .iter()
+            .filter_map(|expense| {
+                defaults
+                    .iter()
+                    .find(|d| d.category_id == expense.category_id)
+                    .map(|d| {
+                        (
+                            expense_taxes::expense_id.eq(expense.id),
+                            expense_taxes::tax_rate.eq(d.tax_rate),
+                            expense_taxes::tax_amount
+                                .eq(calculate_tax(&expense.amount, &d.tax_rate)),
+                            expense_taxes::deductible.eq(d.deductible),
+                        )
+                    })
+            })
+            .collect();
+        if !values.is_empty() {
+            diesel::insert_into(expense_taxes::table)
+                .values(values)
+                .execute(connection)?;
+        }
+    }
+    Ok(())
+}
+
+// Calculates the tax that is included in the given amount at the given rate, rounded to cents.
+fn calculate_tax(amount: &Decimal, tax_rate: &Decimal) -> Decimal {
+    let tax = *amount * *tax_rate / (Decimal::new(100, 0) + *tax_rate);
+    tax.round_dp(2)
+}
+
+// Checks that the tax rate is a valid percentage.
+fn validate_tax_rate(tax_rate: &Decimal) -> Result<(), TaxErrorKind> {
+    if *tax_rate < Decimal::new(0, 2) || *tax_rate >= Decimal::new(100, 0) {
+        return Err(TaxErrorKind::InvalidTaxRate);
+    }
+    Ok(())
+}
+
+// Quotes a CSV field if it contains a separator, quote or line break. Fields that start with a
+// character that spreadsheet applications interpret as a formula, or with a tab or carriage return,
+// are prefixed with a quote.
+fn escape_csv_field(field: &str) -> String {
+    let field = if field.starts_with(&['=', '+', '-', '@', '\t', '\r'][..]) {
+        format!("'{}", field)
+    } else {
+        field.to_string()
+    };
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_test::*;
+    use crate::{establish_connection, get_database_url};
+    use app::AppConfig;
+    use diesel::result::Error;
+    use std::str::FromStr;
+
+    // Tests setting and removing the tax information of expenses.
+    #[test]
+    fn test_expense_tax() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = create_test_category(&conn, &user);
+            let amount = Decimal::from_str("121.00").unwrap();
+            let expense = crate::expense::create(&conn, &user, &amount, &cat, None, None).unwrap();
+            assert_eq!(None, read(&conn, &expense));
+
+            // The tax rate and amount should be valid.
+            for rate in &["-1.00", "100.00"] {
+                let rate = Decimal::from_str(rate).unwrap();
+                assert_eq!(
+                    TaxErrorKind::InvalidTaxRate,
+                    set_expense_tax(&conn, &expense, &rate, None, true).unwrap_err()
+                );
+            }
+            let rate = Decimal::from_str("21.00").unwrap();
+            for tax_amount in &["-0.01", "121.01"] {
+                let tax_amount = Decimal::from_str(tax_amount).unwrap();
+                assert_eq!(
+                    TaxErrorKind::InvalidTaxAmount,
+                    set_expense_tax(&conn, &expense, &rate, Some(&tax_amount), true).unwrap_err()
+                );
+            }
+
+            // The tax amount is calculated if it is not given.
+            let tax = set_expense_tax(&conn, &expense, &rate, None, true).unwrap();
+            assert_eq!(Decimal::from_str("21.00").unwrap(), tax.tax_amount);
+            assert!(tax.deductible);
+            assert_eq!(Some(tax), read(&conn, &expense));
+
+            // Setting the tax again overwrites it.
+            let tax_amount = Decimal::from_str("20.99").unwrap();
+            let tax = set_expense_tax(&conn, &expense, &rate, Some(&tax_amount), false).unwrap();
+            assert_eq!(tax_amount, tax.tax_amount);
+            assert!(!tax.deductible);
+            assert_eq!(Some(tax), read(&conn, &expense));
+
+            remove_expense_tax(&conn, &expense).unwrap();
+            assert_eq!(None, read(&conn, &expense));
+            assert_eq!(
+                TaxErrorKind::NotFound(expense.id),
+                remove_expense_tax(&conn, &expense).unwrap_err()
+            );
+
+            Ok(())
+        });
+    }
+
+    // Tests that the category defaults are applied to new expenses.
+    #[test]
+    fn test_category_defaults() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let office = create_test_category(&conn, &user);
+            let groceries = create_test_category(&conn, &user);
+            assert_eq!(None, get_category_default(&conn, &office));
+
+            let rate = Decimal::from_str("21.00").unwrap();
+            let default = set_category_default(&conn, &office, &rate, true).unwrap();
+            assert_eq!(Some(default), get_category_default(&conn, &office));
+
+            let amount = Decimal::from_str("60.50").unwrap();
+            let expense =
+                crate::expense::create(&conn, &user, &amount, &office, None, None).unwrap();
+            let expected = ExpenseTax {
+                expense_id: expense.id,
+                tax_rate: rate,
+                tax_amount: Decimal::from_str("10.50").unwrap(),
+                deductible: true,
+            };
+            assert_eq!(Some(expected), read(&conn, &expense));
+
+            // Expenses in categories without defaults have no tax information.
+            let expense =
+                crate::expense::create(&conn, &user, &amount, &groceries, None, None).unwrap();
+            assert_eq!(None, read(&conn, &expense));
+
+            // The defaults are also applied when creating multiple expenses at once.
+            let new_expenses = [&office, &groceries]
+                .iter()
+                .map(|category| crate::expense::NewExpense {
+                    amount,
+                    category,
+                    description: None,
+                    date: None,
+                    import_reference: None,
+                })
+                .collect::<Vec<_>>();
+            let expenses = crate::expense::create_many(&conn, &user, &new_expenses)
+                .unwrap()
+                .created;
+            for expense in &expenses {
+                assert_eq!(
+                    expense.category_id == office.id,
+                    read(&conn, expense).is_some()
+                );
+            }
+
+            remove_category_default(&conn, &office).unwrap();
+            assert_eq!(None, get_category_default(&conn, &office));
+            let expense = create_test_expense(&conn, &user, &office);
+            assert_eq!(None, read(&conn, &expense));
+
+            Ok(())
+        });
+    }
+
+    // Tests get_yearly_report() and TaxReport::to_csv().
+    #[test]
+    fn test_yearly_report() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            let cat = crate::category::create(&conn, &user, "Office", None, None).unwrap();
+            let mut expenses = vec![];
+            for (amount, description, date, rate, deductible) in &[
+                ("121.00", Some("Desk, oak"), "2020-03-01", "21.00", true),
+                ("10.60", Some("Book \"Rust\""), "2020-01-15", "6.00", false),
+                // Expenses in other years are not included.
+                ("50.00", None, "2019-12-31", "21.00", true),
+                ("50.00", None, "2021-01-01", "21.00", true),
+            ] {
+                let amount = Decimal::from_str(amount).unwrap();
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+                let expense =
+                    crate::expense::create(&conn, &user, &amount, &cat, *description, Some(&date))
+                        .unwrap();
+                let rate = Decimal::from_str(rate).unwrap();
+                set_expense_tax(&conn, &expense, &rate, None, *deductible).unwrap();
+                expenses.push(expense);
+            }
+
+            // Expenses without tax information are not included.
+            let date = NaiveDate::from_ymd(2020, 6, 1);
+            crate::expense::create(&conn, &user, &Decimal::new(999, 2), &cat, None, Some(&date))
+                .unwrap();
+
+            let report = get_yearly_report(&conn, &user, 2020).unwrap();
+            assert_eq!(2020, report.year);
+            let ids: Vec<i32> = report.lines.iter().map(|l| l.expense.id).collect();
+            assert_eq!(vec![expenses[1].id, expenses[0].id], ids);
+            assert_eq!(Decimal::from_str("131.60").unwrap(), report.total_amount);
+            assert_eq!(Decimal::from_str("21.60").unwrap(), report.total_tax);
+            assert_eq!(Decimal::from_str("21.00").unwrap(), report.deductible_tax);
+
+            let expected = "Date,Category,Description,Amount,Tax rate,Tax amount,Deductible\r\n\
+                            2020-01-15,Office,\"Book \"\"Rust\"\"\",10.60,6.00,0.60,no\r\n\
+                            2020-03-01,Office,\"Desk, oak\",121.00,21.00,21.00,yes\r\n";
+            assert_eq!(expected, report.to_csv());
+
+            // The expenses of other users are not included.
+            let other_user = create_test_user(&conn, &config);
+            assert!(get_yearly_report(&conn, &other_user, 2020)
+                .unwrap()
+                .lines
+                .is_empty());
+
+            Ok(())
+        });
+    }
+
+    // Tests calculate_tax().
+    #[test]
+    fn test_calculate_tax() {
+        let test_cases = [
+            ("121.00", "21.00", "21.00"),
+            ("100.00", "21.00", "17.36"),
+            ("10.60", "6.00", "0.60"),
+            ("9.99", "0.00", "0.00"),
+            ("0.01", "21.00", "0.00"),
+        ];
+
+        for (amount, rate, expected) in &test_cases {
+            let amount = Decimal::from_str(amount).unwrap();
+            let rate = Decimal::from_str(rate).unwrap();
+            let expected = Decimal::from_str(expected).unwrap();
+            assert_eq!(expected, calculate_tax(&amount, &rate));
+        }
+    }
+
+    // Tests that get_yearly_report() returns an error for years outside of the supported range.
+    #[test]
+    fn test_yearly_report_invalid_year() {
+        let conn = establish_connection(&get_database_url()).unwrap();
+        let config = AppConfig::from_test_defaults();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, &config);
+            for year in &[-300_000, 300_000, i32::MAX] {
+                assert_eq!(
+                    TaxErrorKind::InvalidYear(*year),
+                    get_yearly_report(&conn, &user, *year).unwrap_err()
+                );
+            }
+
+            Ok(())
+        });
+    }
+
+    // Tests escape_csv_field().
+    #[test]
+    fn test_escape_csv_field() {
+        let test_cases = [
+            ("Desk", "Desk"),
+            ("Desk, oak", "\"Desk, oak\""),
+            ("Book \"Rust\"", "\"Book \"\"Rust\"\"\""),
+            ("Two\nlines", "\"Two\nlines\""),
+            // Fields that could be interpreted as a formula are neutralized.
+            ("=SUM(A1:A2)", "'=SUM(A1:A2)"),
+            ("+31 6 1234", "'+31 6 1234"),
+            ("-1", "'-1"),
+            ("@cmd", "'@cmd"),
+            ("\t=1+1", "'\t=1+1"),
+            ("\r=1+1", "\"'\r=1+1\""),
+            (
+                "=HYPERLINK(\"x\",\"y\")",
+                "\"'=HYPERLINK(\"\"x\"\",\"\"y\"\")\"",
+            ),
+            ("Tax-free", "Tax-free"),
+        ];
+
+        for (field, expected) in &test_cases {
+            assert_eq!(*expected, escape_csv_field(field));
+        }
+    }
+}
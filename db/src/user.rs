@@ -1,5 +1,6 @@
 // Todo: Add a function for updating a user.
 use super::schema::users;
+use super::{AsyncError, ConnectionPool};
 use app::AppConfig;
 use argonautica::Hasher;
 use diesel::pg::PgConnection;
@@ -204,12 +205,67 @@ pub fn activate(connection: &PgConnection, user: User) -> Result<User, UserError
     Ok(user)
 }
 
+/// Creates a user without blocking the async executor.
+pub async fn create_async(
+    pool: &ConnectionPool,
+    email: &str,
+    password: &str,
+    config: &AppConfig,
+) -> Result<User, AsyncError<UserErrorKind>> {
+    let (email, password, config) = (email.to_string(), password.to_string(), config.clone());
+    super::run(pool, move |connection| {
+        create(connection, &email, &password, &config)
+    })
+    .await
+}
+
+/// Deletes the user with the given email without blocking the async executor.
+pub async fn delete_async(
+    pool: &ConnectionPool,
+    email: &str,
+) -> Result<(), AsyncError<UserErrorKind>> {
+    let email = email.to_string();
+    super::run(pool, move |connection| delete(connection, &email)).await
+}
+
+/// Retrieves the user with the given email address without blocking the async executor.
+pub async fn read_async(
+    pool: &ConnectionPool,
+    email: &str,
+) -> Result<User, AsyncError<UserErrorKind>> {
+    let email = email.to_string();
+    super::run(pool, move |connection| read(connection, &email)).await
+}
+
+/// Verifies that the given email and password are valid without blocking the async executor.
+/// Returns the user if they match.
+pub async fn verify_password_async(
+    pool: &ConnectionPool,
+    email: &str,
+    password: &str,
+    config: &AppConfig,
+) -> Result<User, AsyncError<UserErrorKind>> {
+    let (email, password, config) = (email.to_string(), password.to_string(), config.clone());
+    super::run(pool, move |connection| {
+        verify_password(connection, &email, &password, &config)
+    })
+    .await
+}
+
+/// Activates the given user without blocking the async executor.
+pub async fn activate_async(
+    pool: &ConnectionPool,
+    user: User,
+) -> Result<User, AsyncError<UserErrorKind>> {
+    super::run(pool, move |connection| activate(connection, user)).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::asserts::*;
     use super::*;
 
-    use crate::{establish_connection, get_database_url};
+    use crate::{create_test_connection_pool, establish_connection, get_database_url};
 
     use diesel::result::Error;
 
@@ -406,6 +462,41 @@ mod tests {
         });
     }
 
+    // Tests the asynchronous CRUD functions.
+    #[actix_rt::test]
+    async fn test_crud_async() {
+        let pool = create_test_connection_pool(&get_database_url()).unwrap();
+        let email = "test@example.com";
+        let password = "mypass";
+        let config = AppConfig::from_test_defaults();
+
+        let user = create_async(&pool, email, password, &config).await.unwrap();
+        assert_eq!(user.email, email);
+        assert_eq!(user.activated, false);
+
+        // Errors returned by the synchronous functions are passed on.
+        let same_email_user = create_async(&pool, email, password, &config)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            same_email_user,
+            AsyncError::Query(UserErrorKind::UserWithEmailAlreadyExists(email.to_string()))
+        );
+
+        let user = verify_password_async(&pool, email, password, &config)
+            .await
+            .unwrap();
+        let user = activate_async(&pool, user).await.unwrap();
+        assert_eq!(user.activated, true);
+        assert_eq!(read_async(&pool, email).await.unwrap().activated, true);
+
+        delete_async(&pool, email).await.unwrap();
+        assert_eq!(
+            read_async(&pool, email).await.unwrap_err(),
+            AsyncError::Query(UserErrorKind::UserNotFound(email.to_string()))
+        );
+    }
+
     #[test]
     fn test_verify_password() {
         let connection = establish_connection(&get_database_url()).unwrap();
@@ -0,0 +1,112 @@
+use crate::bootstrap_components::{Alert, AlertType};
+use crate::get_tera_context;
+use crate::user::{assert_authenticated, current_user};
+use actix_identity::Identity;
+use actix_web::{error, web, Error, HttpResponse};
+use db::category::CategoryErrorKind;
+use db::category_template::TEMPLATES;
+use db::AsyncError;
+
+// The query parameters of the category templates page.
+#[derive(Serialize, Deserialize)]
+pub struct TemplatesQuery {
+    // The number of categories that were created by the last import, if any.
+    imported: Option<usize>,
+}
+
+// The submitted form data when importing a category template. Either the name of a bundled
+// template or a template in JSON format, e.g. one that was exported on the command line.
+#[derive(Serialize, Deserialize)]
+pub struct ImportForm {
+    template: Option<String>,
+    json: Option<String>,
+}
+
+// Request handler for the overview of the bundled category templates.
+pub async fn templates_handler(
+    id: Identity,
+    tera: web::Data<tera::Tera>,
+    query: web::Query<TemplatesQuery>,
+) -> Result<HttpResponse, Error> {
+    assert_authenticated(&id)?;
+
+    render_templates_page(id, tera, query.imported, "", vec![])
+}
+
+// Request handler for importing a bundled or pasted category template into the categories of the
+// user.
+pub async fn import_submit(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+    tera: web::Data<tera::Tera>,
+    input: web::Form<ImportForm>,
+) -> Result<HttpResponse, Error> {
+    assert_authenticated(&id)?;
+
+    let categories = match (&input.template, &input.json) {
+        (Some(name), _) => db::category_template::get_template(name)
+            .ok_or_else(|| error::ErrorNotFound("The category template does not exist."))?
+            .categories()
+            .map_err(error::ErrorInternalServerError)?,
+        (None, Some(json)) if !json.trim().is_empty() => match serde_json::from_str(json) {
+            Ok(categories) => categories,
+            Err(_) => return render_malformed_template(id, tera, json),
+        },
+        _ => {
+            return Err(error::ErrorBadRequest(
+                "No category template was submitted.",
+            ))
+        }
+    };
+
+    let user = current_user(&pool, &id).await?;
+    let imported = match db::category_template::import_async(&pool, &user, categories).await {
+        Ok(imported) => imported,
+        Err(AsyncError::Query(CategoryErrorKind::MalformedCategoryList)) => {
+            return render_malformed_template(id, tera, input.json.as_deref().unwrap_or_default());
+        }
+        Err(e) => return Err(error::ErrorInternalServerError(e)),
+    };
+
+    Ok(HttpResponse::SeeOther()
+        .header(
+            "location",
+            format!("/categories/templates?imported={}", imported),
+        )
+        .finish())
+}
+
+// Shows the category templates page again with an error message, keeping the pasted template.
+fn render_malformed_template(
+    id: Identity,
+    tera: web::Data<tera::Tera>,
+    json: &str,
+) -> Result<HttpResponse, Error> {
+    let alert = Alert {
+        alert_type: AlertType::Danger,
+        message: "The category template is malformed. Please paste a template that was exported on the command line.".to_string(),
+    };
+    render_templates_page(id, tera, None, json, vec![alert])
+}
+
+// Renders the category templates page.
+fn render_templates_page(
+    id: Identity,
+    tera: web::Data<tera::Tera>,
+    imported: Option<usize>,
+    json: &str,
+    alerts: Vec<Alert>,
+) -> Result<HttpResponse, Error> {
+    let mut context = get_tera_context("Category templates", id);
+    context.insert("templates", TEMPLATES);
+    context.insert("imported", &imported);
+    context.insert("json", json);
+    if !alerts.is_empty() {
+        context.insert("alerts", &alerts);
+    }
+
+    let content = tera
+        .render("category_templates.html", &context)
+        .map_err(|err| error::ErrorInternalServerError(format!("Template error: {:?}", err)))?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(content))
+}
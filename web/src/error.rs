@@ -14,6 +14,7 @@ pub fn error_handlers() -> ErrorHandlers<Body> {
     ErrorHandlers::new()
         .handler(StatusCode::FORBIDDEN, forbidden)
         .handler(StatusCode::NOT_FOUND, not_found)
+        .handler(StatusCode::SERVICE_UNAVAILABLE, service_unavailable)
 }
 
 // Error handler for a 404 Page not found error.
@@ -31,24 +32,43 @@ fn not_found<B>(res: ServiceResponse<B>) -> Result<ErrorHandlerResponse<B>> {
 
 // Error handler for a 403 Forbidden error.
 fn forbidden(res: ServiceResponse<Body>) -> Result<ErrorHandlerResponse<Body>> {
-    let resp = res.response();
-    let default_message = "Please log in and try again";
-    let message = if let ResponseBody::Body(body) = resp.body() {
-        // Convert the response in Bytes to a string slice.
-        match body {
-            Body::Bytes(b) => std::str::from_utf8(b).unwrap_or(default_message),
-            _ => default_message,
-        }
-    } else {
-        default_message
-    };
-
+    let message = get_body_message(&res, "Please log in and try again");
     let response = get_response(&res, "Access denied", message, None);
     Ok(ErrorHandlerResponse::Response(
         res.into_response(response.into_body()),
     ))
 }
 
+// Error handler for a 503 Service Unavailable error.
+fn service_unavailable(res: ServiceResponse<Body>) -> Result<ErrorHandlerResponse<Body>> {
+    let message = get_body_message(&res, "Firetrack is temporarily unavailable");
+    let response = get_response(
+        &res,
+        "Service unavailable",
+        message,
+        Some("Please try again in a few minutes."),
+    );
+    Ok(ErrorHandlerResponse::Response(
+        res.into_response(response.into_body()),
+    ))
+}
+
+// Returns the body of the given response as a string slice, or the default message if the body
+// does not contain text.
+fn get_body_message<'a>(res: &'a ServiceResponse<Body>, default_message: &'a str) -> &'a str {
+    let body = match res.response().body() {
+        // This is `Body` for responses from route handlers, and `Other` for responses from
+        // middleware.
+        ResponseBody::Body(b) => b,
+        ResponseBody::Other(o) => o,
+    };
+    // Convert the response in Bytes to a string slice.
+    match body {
+        Body::Bytes(b) => std::str::from_utf8(b).unwrap_or(default_message),
+        _ => default_message,
+    }
+}
+
 fn get_response<B>(
     res: &ServiceResponse<B>,
     title: &str,
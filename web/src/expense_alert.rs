@@ -0,0 +1,77 @@
+use crate::get_tera_context;
+use crate::user::{assert_authenticated, current_user};
+use actix_identity::Identity;
+use actix_web::{error, web, Error, HttpResponse};
+use db::expense_alert::{AlertStatus, ExpenseAlertErrorKind};
+use db::user::User;
+use db::AsyncError;
+
+// Request handler for the overview of expense alerts. Lists the expenses that look unusual and
+// have not been reviewed yet.
+pub async fn alerts_handler(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+    tera: web::Data<tera::Tera>,
+) -> Result<HttpResponse, Error> {
+    assert_authenticated(&id)?;
+
+    let user = current_user(&pool, &id).await?;
+    let alerts = db::expense_alert::get_pending_async(&pool, &user)
+        .await
+        .map_err(error::ErrorInternalServerError)?;
+
+    let mut context = get_tera_context("Expense alerts", id);
+    // The "alerts" key is used for status messages, so use a different one.
+    context.insert("expense_alerts", &alerts);
+
+    let content = tera
+        .render("expense_alerts.html", &context)
+        .map_err(|err| error::ErrorInternalServerError(format!("Template error: {:?}", err)))?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(content))
+}
+
+// Request handler for confirming that the expense of an alert is correct.
+pub async fn confirm_submit(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    resolve(id, pool, path.into_inner(), AlertStatus::Confirmed).await
+}
+
+// Request handler for dismissing an alert.
+pub async fn dismiss_submit(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    resolve(id, pool, path.into_inner(), AlertStatus::Dismissed).await
+}
+
+// Sets the status of the alert with the given ID and redirects back to the overview.
+async fn resolve(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+    alert_id: i32,
+    status: AlertStatus,
+) -> Result<HttpResponse, Error> {
+    assert_authenticated(&id)?;
+
+    let user = current_user(&pool, &id).await?;
+    match db::expense_alert::resolve_async(&pool, &user, alert_id, status).await {
+        Ok(_) => Ok(HttpResponse::SeeOther()
+            .header("location", "/alerts")
+            .finish()),
+        Err(AsyncError::Query(ExpenseAlertErrorKind::NotFound(_))) => {
+            Err(error::ErrorNotFound("The alert does not exist."))
+        }
+        Err(e) => Err(error::ErrorInternalServerError(e)),
+    }
+}
+
+// Returns the number of alerts that still need to be reviewed by the given user.
+pub async fn count_pending(pool: &db::ConnectionPool, user: &User) -> Result<i64, Error> {
+    db::expense_alert::count_pending_async(pool, user)
+        .await
+        .map_err(error::ErrorInternalServerError)
+}
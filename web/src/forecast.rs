@@ -0,0 +1,28 @@
+use crate::user::{assert_authenticated, current_user};
+use actix_identity::Identity;
+use actix_web::{error, web, Error, HttpResponse};
+use db::forecast::CategoryForecast;
+use db::user::User;
+
+// Request handler for the spending forecast API endpoint. Returns the projected spending per
+// category at the end of the current month as JSON data.
+pub async fn forecast_handler(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+) -> Result<HttpResponse, Error> {
+    assert_authenticated(&id)?;
+
+    let user = current_user(&pool, &id).await?;
+    Ok(HttpResponse::Ok().json(get_forecast(&pool, &user).await?))
+}
+
+// Returns the projected spending at the end of the current month for the given user.
+pub async fn get_forecast(
+    pool: &db::ConnectionPool,
+    user: &User,
+) -> Result<Vec<CategoryForecast>, Error> {
+    let today = chrono::Local::today().naive_local();
+    db::forecast::get_forecast_async(pool, user, &today)
+        .await
+        .map_err(error::ErrorInternalServerError)
+}
@@ -0,0 +1,128 @@
+use super::super::*;
+use crate::integration_tests::{build_test_app, build_test_app_with_pool, log_in};
+use actix_web::http::StatusCode;
+use actix_web::{dev::Service, test};
+use serde_json::json;
+
+// Integration test checking that category templates can only be imported by logged in users.
+#[actix_rt::test]
+async fn test_category_templates_require_authentication() {
+    let mut app = build_test_app().await;
+
+    let req = test::TestRequest::get()
+        .uri("/categories/templates")
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/categories/templates/import")
+        .set_form(&[("template", "freelancer")])
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+// Tests importing bundled and pasted category templates.
+#[actix_rt::test]
+async fn test_import_category_templates() {
+    let config = AppConfig::from_test_defaults();
+    let (mut app, pool) = build_test_app_with_pool(config.clone()).await;
+    let (user, cookie) = log_in(&mut app, &pool, &config, "templates@example.com").await;
+
+    let req = test::TestRequest::get()
+        .uri("/categories/templates")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    let body = get_response_body(response.response());
+    assert_page_title(&body, "Category templates");
+    assert!(body.contains("Freelancer"));
+    assert!(!body.contains("The template was imported."));
+
+    // Import a bundled template.
+    let req = test::TestRequest::post()
+        .uri("/categories/templates/import")
+        .cookie(cookie.clone())
+        .set_form(&[("template", "freelancer")])
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    let expected = db::category_template::get_template("freelancer")
+        .unwrap()
+        .categories()
+        .unwrap();
+    let created = db::category::get_categories_async(&pool, &user)
+        .await
+        .unwrap()
+        .len();
+    assert!(created > 0);
+    assert_response_see_other(
+        response.response(),
+        &format!("/categories/templates?imported={}", created),
+    );
+    let exported = {
+        let connection = pool.get().unwrap();
+        db::category_template::export(&connection, &user).unwrap()
+    };
+    assert_eq!(expected, exported);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/categories/templates?imported={}", created))
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    assert!(get_response_body(response.response()).contains(&format!(
+        "The template was imported. {} new categories were created.",
+        created
+    )));
+
+    // Import a pasted template. Existing categories are reused.
+    let json = json!({"business ": ["Pasted category"], "Pets": ["Food", "Vet"]}).to_string();
+    let req = test::TestRequest::post()
+        .uri("/categories/templates/import")
+        .cookie(cookie.clone())
+        .set_form(&[("json", json.as_str())])
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_see_other(response.response(), "/categories/templates?imported=4");
+
+    // Malformed templates are rejected and shown again.
+    for json in &["{\"Food\": ", "{\"Food\": [\"Groceries\", 1]}"] {
+        let req = test::TestRequest::post()
+            .uri("/categories/templates/import")
+            .cookie(cookie.clone())
+            .set_form(&[("json", *json)])
+            .to_request();
+        let response = app.call(req).await.unwrap();
+        assert_response_ok(response.response());
+        let body = get_response_body(response.response());
+        assert!(body.contains("The category template is malformed."));
+        assert!(body.contains(&json.replace('"', "&quot;")));
+    }
+    assert_eq!(
+        created + 4,
+        db::category::get_categories_async(&pool, &user)
+            .await
+            .unwrap()
+            .len()
+    );
+
+    // Non-existing bundled templates are not found, and an empty form is a bad request.
+    let req = test::TestRequest::post()
+        .uri("/categories/templates/import")
+        .cookie(cookie.clone())
+        .set_form(&[("template", "non-existing")])
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post()
+        .uri("/categories/templates/import")
+        .cookie(cookie)
+        .set_form(&[("json", " ")])
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,107 @@
+use super::super::*;
+use crate::integration_tests::{build_test_app, build_test_app_with_pool, log_in};
+use actix_web::http::StatusCode;
+use actix_web::{dev::Service, test};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+// Integration test checking that the expense alerts can only be accessed by logged in users.
+#[actix_rt::test]
+async fn test_expense_alerts_require_authentication() {
+    let mut app = build_test_app().await;
+
+    let req = test::TestRequest::get().uri("/alerts").to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    for action in &["confirm", "dismiss"] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/alerts/1/{}", action))
+            .to_request();
+        let response = app.call(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
+
+// Tests reviewing the expense alerts as a logged in user.
+#[actix_rt::test]
+async fn test_expense_alerts() {
+    let config = AppConfig::from_test_defaults();
+    let (mut app, pool) = build_test_app_with_pool(config.clone()).await;
+    let (user, cookie) = log_in(&mut app, &pool, &config, "expense-alerts@example.com").await;
+
+    let req = test::TestRequest::get()
+        .uri("/alerts")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    let body = get_response_body(response.response());
+    assert_page_title(&body, "Expense alerts");
+    assert!(body.contains("There are no expenses to review."));
+
+    // Entering the same expense three times results in two alerts for possible duplicates.
+    let category = db::category::create_async(&pool, &user, "Groceries", None, None)
+        .await
+        .unwrap();
+    let amount = Decimal::from_str("12.99").unwrap();
+    for _ in 0..3 {
+        db::expense::create_async(&pool, &user, &amount, &category, Some("Supermarket"), None)
+            .await
+            .unwrap();
+    }
+    let alerts = db::expense_alert::get_pending_async(&pool, &user)
+        .await
+        .unwrap();
+    assert_eq!(2, alerts.len());
+
+    // The number of pending alerts is shown on the homepage.
+    let req = test::TestRequest::get()
+        .uri("/")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    assert!(get_response_body(response.response()).contains("2 expenses look unusual."));
+
+    let req = test::TestRequest::get()
+        .uri("/alerts")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    let body = get_response_body(response.response());
+    assert!(body.contains("might be a duplicate."));
+    assert!(body.contains("Supermarket"));
+
+    // Confirm the first alert and dismiss the second.
+    for ((alert, _), action) in alerts.iter().zip(&["confirm", "dismiss"]) {
+        let req = test::TestRequest::post()
+            .uri(&format!("/alerts/{}/{}", alert.id, action))
+            .cookie(cookie.clone())
+            .to_request();
+        let response = app.call(req).await.unwrap();
+        assert_response_see_other(response.response(), "/alerts");
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert!(!get_response_body(response.response()).contains("look unusual"));
+
+    let req = test::TestRequest::get()
+        .uri("/alerts")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert!(get_response_body(response.response()).contains("There are no expenses to review."));
+
+    // Resolving a non-existing alert results in a 404.
+    let req = test::TestRequest::post()
+        .uri(&format!("/alerts/{}/confirm", alerts[0].0.id + 1000))
+        .cookie(cookie)
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
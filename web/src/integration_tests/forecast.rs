@@ -0,0 +1,94 @@
+use super::super::*;
+use crate::integration_tests::{build_test_app, build_test_app_with_pool, log_in};
+use actix_web::http::StatusCode;
+use actix_web::{dev::Service, test};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+// Integration test for the spending forecast API endpoint.
+#[actix_rt::test]
+async fn test_forecast_requires_authentication() {
+    let mut app = build_test_app().await;
+
+    let req = test::TestRequest::get().uri("/api/forecast").to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+// Tests that the forecast is returned by the API endpoint and shown on the homepage.
+#[actix_rt::test]
+async fn test_forecast() {
+    let config = AppConfig::from_test_defaults();
+    let (mut app, pool) = build_test_app_with_pool(config.clone()).await;
+    let (user, cookie) = log_in(&mut app, &pool, &config, "forecast@example.com").await;
+
+    // Without expenses there is nothing to forecast.
+    let req = test::TestRequest::get()
+        .uri("/api/forecast")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    assert_eq!("[]", get_response_body(response.response()));
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    assert!(!get_response_body(response.response()).contains("Spending forecast"));
+
+    // Spend some money today.
+    let category = db::category::create_async(&pool, &user, "Groceries", None, None)
+        .await
+        .unwrap();
+    let amount = Decimal::from_str("12.34").unwrap();
+    db::expense::create_async(&pool, &user, &amount, &category, None, None)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/api/forecast")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    let forecast: serde_json::Value =
+        serde_json::from_str(&get_response_body(response.response())).unwrap();
+    assert_eq!(1, forecast.as_array().unwrap().len());
+    assert_eq!(category.id, forecast[0]["category_id"]);
+    assert_eq!("Groceries", forecast[0]["category_name"]);
+    assert_eq!("12.34", forecast[0]["spent"]);
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    let body = get_response_body(response.response());
+    assert!(body.contains("Spending forecast"));
+    assert!(body.contains(" on Groceries."));
+
+    // When the user no longer exists the homepage for anonymous users is shown.
+    db::user::delete_async(&pool, "forecast@example.com")
+        .await
+        .unwrap();
+    let req = test::TestRequest::get()
+        .uri("/")
+        .cookie(cookie)
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    let body = get_response_body(response.response());
+    assert!(!body.contains("Spending forecast"));
+    assert_page(
+        &body,
+        PageAssertOptions {
+            title: Some("Home".to_string()),
+            has_sidebar: false,
+            ..PageAssertOptions::default()
+        },
+    );
+}
@@ -0,0 +1,55 @@
+use super::super::*;
+use crate::integration_tests::{build_test_app, build_test_app_with_config};
+use actix_web::dev::Service;
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use app::SiteMode;
+
+// Integration test for the metrics endpoint.
+#[actix_rt::test]
+async fn test_metrics() {
+    // The metrics endpoint is disabled if no token is configured.
+    let mut app = build_test_app().await;
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The metrics should be available regardless of the site mode.
+    let mut config = AppConfig::from_test_defaults();
+    config.set_site_mode(SiteMode::Maintenance);
+    config.set_metrics_token(Some("my_metrics_token".to_string()));
+    let mut app = build_test_app_with_config(config).await;
+
+    // Requests without a valid token are rejected.
+    for authorization in &[None, Some("Bearer wrong_token"), Some("my_metrics_token")] {
+        let mut req = test::TestRequest::get().uri("/metrics");
+        if let Some(authorization) = authorization {
+            req = req.header(header::AUTHORIZATION, *authorization);
+        }
+        let response = app.call(req.to_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/metrics")
+        .header(header::AUTHORIZATION, "Bearer my_metrics_token")
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+
+    let body = get_response_body(response.response());
+    for metric in &[
+        "firetrack_db_pool_connections_max",
+        "firetrack_db_pool_connections_in_use",
+        "firetrack_db_pool_connections_idle",
+        "firetrack_db_pool_checkouts_total",
+        "firetrack_db_pool_checkout_timeouts_total",
+        "firetrack_db_pool_wait_seconds_total",
+        "firetrack_db_slow_queries_total",
+    ] {
+        assert!(body.contains(format!("# TYPE {} ", metric).as_str()));
+        assert!(body
+            .lines()
+            .any(|line| line.starts_with(format!("{} ", metric).as_str())));
+    }
+}
@@ -2,11 +2,22 @@ use crate::configure_application;
 
 use actix_http::{body::Body, error::Error, Request};
 use actix_service::Service;
+use actix_web::cookie::Cookie;
+use actix_web::http::StatusCode;
 use actix_web::{dev::ServiceResponse, test, App};
 use app::AppConfig;
+use db::user::User;
 
+pub mod category_template;
 pub mod error;
+pub mod expense_alert;
+pub mod forecast;
 pub mod homepage;
+pub mod metrics;
+pub mod project;
+pub mod recurring;
+pub mod site_mode;
+pub mod tax;
 pub mod user;
 
 /// Returns the Firetrack web application using the default test configuration.
@@ -16,11 +27,66 @@ pub async fn build_test_app(
     dotenv::dotenv().ok();
     dotenv::from_filename(".env.dist").ok();
 
-    let config = AppConfig::from_test_defaults();
+    build_test_app_with_config(AppConfig::from_test_defaults()).await
+}
+
+/// Returns the Firetrack web application using the given configuration.
+pub async fn build_test_app_with_config(
+    config: AppConfig,
+) -> impl Service<Request = Request, Response = ServiceResponse<Body>, Error = Error> {
+    build_test_app_with_pool(config).await.0
+}
+
+/// Returns the Firetrack web application using the given configuration, together with its database
+/// connection pool. The pool can be used to prepare test data.
+///
+/// Every connection in the pool has its own test transaction. Connections are reused in the order
+/// they are returned, so use the async database functions, which return the connection right away,
+/// to make sure the data is visible to the application.
+pub async fn build_test_app_with_pool(
+    config: AppConfig,
+) -> (
+    impl Service<Request = Request, Response = ServiceResponse<Body>, Error = Error>,
+    db::ConnectionPool,
+) {
     let database_url = config.database_url();
     let pool = db::create_test_connection_pool(database_url).unwrap();
-    test::init_service(
+    let app = test::init_service(
         App::new().configure(|c| configure_application(c, pool.clone(), config.clone())),
     )
-    .await
+    .await;
+    (app, pool)
+}
+
+/// Creates a user with the given email address and logs in through the login form. Returns the user
+/// and the cookie that authenticates subsequent requests.
+pub async fn log_in<S>(
+    app: &mut S,
+    pool: &db::ConnectionPool,
+    config: &AppConfig,
+    email: &str,
+) -> (User, Cookie<'static>)
+where
+    S: Service<Request = Request, Response = ServiceResponse<Body>, Error = Error>,
+{
+    let password = "mypass";
+    let user = db::user::create_async(pool, email, password, config)
+        .await
+        .unwrap();
+
+    let payload = crate::user::UserForm::new(email.to_string(), password.to_string());
+    let req = test::TestRequest::post()
+        .uri("/user/login")
+        .set_form(&payload)
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+    let cookie = response
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == "auth")
+        .expect("The authentication cookie is set.")
+        .into_owned();
+    (user, cookie)
 }
@@ -0,0 +1,90 @@
+use super::super::*;
+use crate::integration_tests::{build_test_app, build_test_app_with_pool, log_in};
+use actix_web::http::StatusCode;
+use actix_web::{dev::Service, test};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+// Integration test checking that the projects can only be accessed by logged in users.
+#[actix_rt::test]
+async fn test_projects_require_authentication() {
+    let mut app = build_test_app().await;
+
+    for uri in &["/projects", "/projects/1"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let response = app.call(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
+
+// Tests the project overview and detail pages as a logged in user.
+#[actix_rt::test]
+async fn test_projects() {
+    let config = AppConfig::from_test_defaults();
+    let (mut app, pool) = build_test_app_with_pool(config.clone()).await;
+    let (user, cookie) = log_in(&mut app, &pool, &config, "projects@example.com").await;
+
+    let req = test::TestRequest::get()
+        .uri("/projects")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    let body = get_response_body(response.response());
+    assert_page_title(&body, "Projects");
+    assert!(body.contains("There are no projects yet."));
+
+    // Create a project with a budget and add an expense to it. The connection is returned to the
+    // pool before the next request so the application can see the data.
+    let project = {
+        let connection = pool.get().unwrap();
+        let budget = Decimal::from_str("1000.00").unwrap();
+        let project = db::project::create(&connection, &user, "Renovation", Some(&budget)).unwrap();
+        let category = db::category::create(&connection, &user, "Paint", None, None).unwrap();
+        let amount = Decimal::from_str("149.99").unwrap();
+        let expense =
+            db::expense::create(&connection, &user, &amount, &category, None, None).unwrap();
+        db::project::add_expense(&connection, &project, &expense).unwrap();
+        project
+    };
+
+    let req = test::TestRequest::get()
+        .uri("/projects")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    let body = get_response_body(response.response());
+    assert!(body.contains(&format!(
+        "<a href=\"/projects/{}\">Renovation</a>",
+        project.id
+    )));
+    assert!(body.contains("Budget: €1000.00"));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/projects/{}", project.id))
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    let body = get_response_body(response.response());
+    assert_page_title(&body, "Renovation");
+    assert!(body.contains("<td>Paint</td>"));
+    assert!(body.contains("€149.99"));
+    assert!(body.contains("€850.01"));
+
+    // Non-existing projects and projects of other users are not found.
+    let other_project = {
+        let connection = pool.get().unwrap();
+        let other_user =
+            db::user::create(&connection, "projects-other@example.com", "mypass", &config).unwrap();
+        db::project::create(&connection, &other_user, "Holiday", None).unwrap()
+    };
+    for id in &[other_project.id, other_project.id + 1000] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/projects/{}", id))
+            .cookie(cookie.clone())
+            .to_request();
+        let response = app.call(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
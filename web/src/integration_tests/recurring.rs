@@ -0,0 +1,103 @@
+use super::super::*;
+use crate::integration_tests::{build_test_app, build_test_app_with_pool, log_in};
+use actix_web::http::StatusCode;
+use actix_web::{dev::Service, test};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+// Integration test checking that the recurring expenses can only be accessed by logged in users.
+#[actix_rt::test]
+async fn test_recurring_expenses_require_authentication() {
+    let mut app = build_test_app().await;
+
+    let req = test::TestRequest::get().uri("/recurring").to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/recurring/accept")
+        .set_form(&[("category_id", "1"), ("description", "Rent")])
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+// Tests accepting a suggested recurring expense as a logged in user.
+#[actix_rt::test]
+async fn test_recurring_expenses() {
+    let config = AppConfig::from_test_defaults();
+    let (mut app, pool) = build_test_app_with_pool(config.clone()).await;
+    let (user, cookie) = log_in(&mut app, &pool, &config, "recurring@example.com").await;
+
+    let req = test::TestRequest::get()
+        .uri("/recurring")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    let body = get_response_body(response.response());
+    assert_page_title(&body, "Recurring expenses");
+    assert!(body.contains("There are no recurring expenses yet."));
+    assert!(!body.contains("Suggested recurring expenses"));
+
+    // Accepting an expense that was not suggested results in a 404.
+    let category = db::category::create_async(&pool, &user, "Housing", None, None)
+        .await
+        .unwrap();
+    let category_id = category.id.to_string();
+    let form = [
+        ("category_id", category_id.as_str()),
+        ("description", "Rent"),
+    ];
+    let req = test::TestRequest::post()
+        .uri("/recurring/accept")
+        .cookie(cookie.clone())
+        .set_form(&form)
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Pay the rent every month.
+    let amount = Decimal::from_str("850.00").unwrap();
+    for date in &["2020-03-01", "2020-04-01", "2020-05-01"] {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        db::expense::create_async(&pool, &user, &amount, &category, Some("Rent"), Some(&date))
+            .await
+            .unwrap();
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/recurring")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    let body = get_response_body(response.response());
+    assert!(body.contains("Suggested recurring expenses"));
+    assert!(body.contains(
+        "Rent (Housing): €850.00 every 31 days, 3 times so far. Next expected on 2020-06-01."
+    ));
+
+    let req = test::TestRequest::post()
+        .uri("/recurring/accept")
+        .cookie(cookie.clone())
+        .set_form(&form)
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_see_other(response.response(), "/recurring");
+
+    // The suggestion became a recurring expense.
+    let req = test::TestRequest::get()
+        .uri("/recurring")
+        .cookie(cookie)
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    let body = get_response_body(response.response());
+    assert!(!body.contains("Suggested recurring expenses"));
+    assert!(body.contains("Rent: €850.00 every 31 days, next on 2020-06-01."));
+    let recurring_expenses = db::recurring::get_recurring_expenses_async(&pool, &user)
+        .await
+        .unwrap();
+    assert_eq!(1, recurring_expenses.len());
+    assert_eq!(category.id, recurring_expenses[0].category_id);
+}
@@ -0,0 +1,95 @@
+use super::super::*;
+use crate::integration_tests::build_test_app_with_config;
+use actix_http::Request;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use app::SiteMode;
+
+// Integration test for the maintenance mode.
+#[actix_rt::test]
+async fn test_maintenance_mode() {
+    let mut config = AppConfig::from_test_defaults();
+    config.set_site_mode(SiteMode::Maintenance);
+    let mut app = build_test_app_with_config(config).await;
+
+    // All pages should return a 503 Service Unavailable error page.
+    for uri in &["/", "/user/login", "/non-existing-path"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let response = app.call(req).await.unwrap();
+        assert_service_unavailable(response.response());
+    }
+
+    let payload = user::UserForm::new("test@example.com".to_string(), "mypass".to_string());
+    let req = test::TestRequest::post()
+        .uri("/user/login")
+        .set_form(&payload)
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_service_unavailable(response.response());
+
+    // The health check should still be available.
+    assert_health_check(&mut app).await;
+}
+
+// Integration test for the read-only mode.
+#[actix_rt::test]
+async fn test_read_only_mode() {
+    let mut config = AppConfig::from_test_defaults();
+    config.set_site_mode(SiteMode::ReadOnly);
+    let mut app = build_test_app_with_config(config).await;
+
+    // Pages can be viewed.
+    for uri in &["/", "/user/login", "/user/register"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let response = app.call(req).await.unwrap();
+        assert_response_ok(response.response());
+    }
+
+    // Submitting the login form is allowed since this doesn't change any data. Since the user does
+    // not exist the form is shown again.
+    let payload = user::UserForm::new("test@example.com".to_string(), "mypass".to_string());
+    let req = test::TestRequest::post()
+        .uri("/user/login")
+        .set_form(&payload)
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+
+    // Registering a new account is not allowed.
+    let req = test::TestRequest::post()
+        .uri("/user/register")
+        .set_form(&payload)
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_service_unavailable(response.response());
+
+    assert_health_check(&mut app).await;
+}
+
+// Checks that the health check returns a 200 OK response.
+async fn assert_health_check<S>(app: &mut S)
+where
+    S: Service<Request = Request, Response = ServiceResponse, Error = Error>,
+{
+    let req = test::TestRequest::get().uri("/health").to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    assert_eq!("OK", get_response_body(response.response()));
+}
+
+// Checks that the response is a 503 Service Unavailable error page.
+fn assert_service_unavailable(response: &HttpResponse) {
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let body = get_response_body(response);
+    assert_page(
+        &body,
+        PageAssertOptions {
+            title: Some("Service unavailable".to_string()),
+            is_error_page: true,
+            has_sidebar: false,
+            ..PageAssertOptions::default()
+        },
+    );
+}
@@ -0,0 +1,84 @@
+use super::super::*;
+use crate::integration_tests::{build_test_app, build_test_app_with_pool, log_in};
+use actix_web::http::{header, StatusCode};
+use actix_web::{dev::Service, test};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+// Integration test checking that the tax report can only be downloaded by logged in users.
+#[actix_rt::test]
+async fn test_tax_report_requires_authentication() {
+    let mut app = build_test_app().await;
+
+    let req = test::TestRequest::get()
+        .uri("/reports/tax/2020")
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+// Tests downloading the yearly tax report.
+#[actix_rt::test]
+async fn test_tax_report() {
+    let config = AppConfig::from_test_defaults();
+    let (mut app, pool) = build_test_app_with_pool(config.clone()).await;
+    let (user, cookie) = log_in(&mut app, &pool, &config, "tax@example.com").await;
+
+    // Create an expense with tax information. The connection is returned to the pool before the
+    // next request so the application can see the data.
+    {
+        let connection = pool.get().unwrap();
+        let category = db::category::create(&connection, &user, "Office", None, None).unwrap();
+        let amount = Decimal::from_str("121.00").unwrap();
+        let date = NaiveDate::from_ymd(2020, 3, 1);
+        let expense = db::expense::create(
+            &connection,
+            &user,
+            &amount,
+            &category,
+            Some("=1+1"),
+            Some(&date),
+        )
+        .unwrap();
+        let rate = Decimal::from_str("21.00").unwrap();
+        db::tax::set_expense_tax(&connection, &expense, &rate, None, true).unwrap();
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/reports/tax/2020")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    let headers = response.headers();
+    assert_eq!("text/csv", headers.get(header::CONTENT_TYPE).unwrap());
+    assert_eq!(
+        "attachment; filename=\"taxes-2020.csv\"",
+        headers.get(header::CONTENT_DISPOSITION).unwrap()
+    );
+    // The description is neutralized so it is not interpreted as a formula.
+    let expected = "Date,Category,Description,Amount,Tax rate,Tax amount,Deductible\r\n\
+                    2020-03-01,Office,'=1+1,121.00,21.00,21.00,yes\r\n";
+    assert_eq!(expected, get_response_body(response.response()));
+
+    // The report of another year is empty.
+    let req = test::TestRequest::get()
+        .uri("/reports/tax/2019")
+        .cookie(cookie.clone())
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_response_ok(response.response());
+    assert_eq!(
+        "Date,Category,Description,Amount,Tax rate,Tax amount,Deductible\r\n",
+        get_response_body(response.response())
+    );
+
+    // Years outside of the supported range are rejected.
+    let req = test::TestRequest::get()
+        .uri("/reports/tax/300000")
+        .cookie(cookie)
+        .to_request();
+    let response = app.call(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
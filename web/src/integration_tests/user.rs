@@ -35,7 +35,7 @@ async fn register_with_valid_data() {
     assert_response_see_other(&response.response(), "/user/activate");
 
     // Check that a user with the given username and password exists in the database.
-    let user = db::user::read(&pool.get().unwrap(), email).unwrap();
+    let user = db::user::read_async(&pool, email).await.unwrap();
 
     assert_eq!(user.email, email);
     assert!(hashed_password_is_valid(
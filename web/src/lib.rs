@@ -10,7 +10,15 @@ mod integration_tests;
 use crate::firetrack_test::*;
 
 mod bootstrap_components;
+mod category_template;
 mod error;
+mod expense_alert;
+mod forecast;
+mod metrics;
+mod project;
+mod recurring;
+mod site_mode;
+mod tax;
 mod user;
 
 use actix_identity::{CookieIdentityPolicy, Identity, IdentityService};
@@ -18,11 +26,15 @@ use actix_session::CookieSession;
 use actix_web::error::ErrorInternalServerError;
 use actix_web::{middleware::Logger, web, App, Error, HttpResponse, HttpServer};
 use app::AppConfig;
+use db::user::UserErrorKind;
+use db::AsyncError;
 use std::env;
+use std::time::Duration;
 
 // Starts the web server on the host address and port as configured in the application.
 pub async fn serve(config: AppConfig) -> Result<(), String> {
     let pool = db::create_connection_pool(&config.database_url()).unwrap();
+    db::metrics::set_slow_query_threshold(Duration::from_millis(config.slow_query_threshold()));
     let cloned_config = config.clone();
 
     // Configure the application.
@@ -46,8 +58,34 @@ pub async fn serve(config: AppConfig) -> Result<(), String> {
 }
 
 // Controller for the homepage.
-async fn index(id: Identity, template: web::Data<tera::Tera>) -> Result<HttpResponse, Error> {
-    let context = get_tera_context("Home", id);
+async fn index(
+    id: Identity,
+    template: web::Data<tera::Tera>,
+    pool: web::Data<db::ConnectionPool>,
+) -> Result<HttpResponse, Error> {
+    // If the user no longer exists, e.g. because the account was deleted while they were logged
+    // in, end the session and show the homepage for anonymous users.
+    let user = match id.identity() {
+        Some(email) => match db::user::read_async(&pool, &email).await {
+            Ok(user) => Some(user),
+            Err(AsyncError::Query(UserErrorKind::UserNotFound(_))) => {
+                id.forget();
+                None
+            }
+            Err(e) => return Err(ErrorInternalServerError(e)),
+        },
+        None => None,
+    };
+    let mut context = get_tera_context("Home", user.as_ref().map(|u| u.email.clone()));
+
+    // Show authenticated users how much they are on track to spend this month, and how many
+    // unusual expenses they still need to review.
+    if let Some(user) = user {
+        let forecast = forecast::get_forecast(&pool, &user).await?;
+        context.insert("forecast", &forecast);
+        let pending_alerts = expense_alert::count_pending(&pool, &user).await?;
+        context.insert("pending_alerts", &pending_alerts);
+    }
 
     let content = template
         .render("index.html", &context)
@@ -55,6 +93,12 @@ async fn index(id: Identity, template: web::Data<tera::Tera>) -> Result<HttpResp
     Ok(HttpResponse::Ok().content_type("text/html").body(content))
 }
 
+// Controller for the health check. This is available regardless of the site mode so that monitoring
+// tools can check the application is running during maintenance.
+async fn health() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain").body("OK")
+}
+
 /// Contains the identity of the current user as a string containing the email address. This is used
 /// so we can instantiate a `tera::Context` struct both from the `actix_identity::Identity` struct
 /// which is available in responses (e.g. route handlers) as a `FromRequest` data extractor, and the
@@ -100,6 +144,7 @@ pub fn configure_application(
 ) {
     let tera = compile_templates();
     let session_key = app_config.session_key();
+    let site_mode = app_config.site_mode();
     config
         .data(tera)
         .data(pool)
@@ -111,10 +156,15 @@ pub fn configure_application(
             "/third-party",
             "web/static/third-party/",
         ))
+        .route("/health", web::get().to(health))
+        .route("/metrics", web::get().to(metrics::metrics_handler))
         .service(
             web::scope("")
-                // Middleware is executed in the reverse order. Define the error handlers first so they
-                // run after the identity and session handlers and can access their data if needed.
+                // Middleware is executed in the reverse order. The site mode guard is defined first
+                // so the error handlers can render its responses.
+                .wrap(site_mode::SiteModeGuard::new(site_mode))
+                // Define the error handlers before the identity and session handlers so they run
+                // after them and can access their data if needed.
                 .wrap(error::error_handlers())
                 // Todo: Allow to toggle the secure flag on both the session and identity providers.
                 // Ref. https://github.com/pfrenssen/firetrack/issues/96
@@ -126,6 +176,32 @@ pub fn configure_application(
                 ))
                 .route("/", web::get().to(index))
                 .route("/favicon.ico", web::get().to(index))
+                .route("/alerts", web::get().to(expense_alert::alerts_handler))
+                .route(
+                    "/alerts/{id}/confirm",
+                    web::post().to(expense_alert::confirm_submit),
+                )
+                .route(
+                    "/alerts/{id}/dismiss",
+                    web::post().to(expense_alert::dismiss_submit),
+                )
+                .route("/api/forecast", web::get().to(forecast::forecast_handler))
+                .route(
+                    "/categories/templates",
+                    web::get().to(category_template::templates_handler),
+                )
+                .route(
+                    "/categories/templates/import",
+                    web::post().to(category_template::import_submit),
+                )
+                .route("/projects", web::get().to(project::projects_handler))
+                .route("/projects/{id}", web::get().to(project::project_handler))
+                .route("/recurring", web::get().to(recurring::recurring_handler))
+                .route(
+                    "/recurring/accept",
+                    web::post().to(recurring::accept_submit),
+                )
+                .route("/reports/tax/{year}", web::get().to(tax::report_handler))
                 .route("/user/activate", web::get().to(user::activate_handler))
                 .route("/user/activate", web::post().to(user::activate_submit))
                 .route("/user/login", web::get().to(user::login_handler))
@@ -0,0 +1,112 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use app::AppConfig;
+use std::fmt::Write;
+
+/// Controller for the metrics endpoint. Returns the state of the database connection pool in the
+/// Prometheus text format. Like the health check this is available regardless of the site mode.
+///
+/// The metrics reveal details about the load on the application, so they are only returned to
+/// clients that send the configured metrics token as a bearer token. The endpoint is disabled if no
+/// token is configured.
+pub async fn metrics_handler(
+    request: HttpRequest,
+    pool: web::Data<db::ConnectionPool>,
+    config: web::Data<AppConfig>,
+) -> HttpResponse {
+    let token = match config.metrics_token() {
+        Some(token) => token,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map_or(false, |provided| constant_time_eq(provided, token));
+    if !authorized {
+        return HttpResponse::Unauthorized()
+            .header(header::WWW_AUTHENTICATE, "Bearer")
+            .finish();
+    }
+
+    let metrics = db::metrics::pool_metrics(&pool);
+
+    let mut body = String::new();
+    write_metric(
+        &mut body,
+        "firetrack_db_pool_connections_max",
+        "gauge",
+        "The maximum number of connections in the database connection pool.",
+        metrics.max_connections,
+    );
+    write_metric(
+        &mut body,
+        "firetrack_db_pool_connections_in_use",
+        "gauge",
+        "The number of database connections that are checked out.",
+        metrics.in_use_connections,
+    );
+    write_metric(
+        &mut body,
+        "firetrack_db_pool_connections_idle",
+        "gauge",
+        "The number of database connections that are open but not checked out.",
+        metrics.idle_connections,
+    );
+    write_metric(
+        &mut body,
+        "firetrack_db_pool_checkouts_total",
+        "counter",
+        "The number of times a database connection was checked out.",
+        metrics.checkouts,
+    );
+    write_metric(
+        &mut body,
+        "firetrack_db_pool_checkout_timeouts_total",
+        "counter",
+        "The number of times no database connection became available in time.",
+        metrics.checkout_timeouts,
+    );
+    write_metric(
+        &mut body,
+        "firetrack_db_pool_wait_seconds_total",
+        "counter",
+        "The total time spent waiting for a database connection.",
+        metrics.checkout_wait_time.as_secs_f64(),
+    );
+    write_metric(
+        &mut body,
+        "firetrack_db_slow_queries_total",
+        "counter",
+        "The number of database operations that exceeded the slow query threshold.",
+        metrics.slow_queries,
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body)
+}
+
+// Appends a metric with its description and type to the given output.
+fn write_metric<T: std::fmt::Display>(
+    output: &mut String,
+    name: &str,
+    metric_type: &str,
+    help: &str,
+    value: T,
+) {
+    // Writing to a string cannot fail.
+    writeln!(output, "# HELP {} {}", name, help).unwrap();
+    writeln!(output, "# TYPE {} {}", name, metric_type).unwrap();
+    writeln!(output, "{} {}", name, value).unwrap();
+}
+
+// Compares the given strings in a time that does not depend on the position of the first
+// difference, so the token cannot be guessed one character at a time.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |difference, (x, y)| difference | (x ^ y))
+            == 0
+}
@@ -0,0 +1,56 @@
+use crate::get_tera_context;
+use crate::user::{assert_authenticated, current_user};
+use actix_identity::Identity;
+use actix_web::{error, web, Error, HttpResponse};
+use db::project::ProjectErrorKind;
+use db::AsyncError;
+
+// Request handler for the overview of the projects and trips of the user.
+pub async fn projects_handler(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+    tera: web::Data<tera::Tera>,
+) -> Result<HttpResponse, Error> {
+    assert_authenticated(&id)?;
+
+    let user = current_user(&pool, &id).await?;
+    let projects = db::project::get_projects_async(&pool, &user)
+        .await
+        .map_err(error::ErrorInternalServerError)?;
+
+    let mut context = get_tera_context("Projects", id);
+    context.insert("projects", &projects);
+
+    let content = tera
+        .render("project/projects.html", &context)
+        .map_err(|err| error::ErrorInternalServerError(format!("Template error: {:?}", err)))?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(content))
+}
+
+// Request handler for the detail page of a project. Shows the amount spent per category and the
+// remaining budget.
+pub async fn project_handler(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+    tera: web::Data<tera::Tera>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    assert_authenticated(&id)?;
+
+    let user = current_user(&pool, &id).await?;
+    let summary = match db::project::get_summary_async(&pool, &user, path.into_inner()).await {
+        Ok(summary) => summary,
+        Err(AsyncError::Query(ProjectErrorKind::NotFound(_))) => {
+            return Err(error::ErrorNotFound("The project does not exist."));
+        }
+        Err(e) => return Err(error::ErrorInternalServerError(e)),
+    };
+
+    let mut context = get_tera_context(&summary.project.name, id);
+    context.insert("summary", &summary);
+
+    let content = tera
+        .render("project/project.html", &context)
+        .map_err(|err| error::ErrorInternalServerError(format!("Template error: {:?}", err)))?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(content))
+}
@@ -0,0 +1,60 @@
+use crate::get_tera_context;
+use crate::user::{assert_authenticated, current_user};
+use actix_identity::Identity;
+use actix_web::{error, web, Error, HttpResponse};
+use db::recurring::RecurringErrorKind;
+use db::AsyncError;
+
+// The submitted form data when accepting a suggested recurring expense.
+#[derive(Serialize, Deserialize)]
+pub struct AcceptForm {
+    category_id: i32,
+    description: String,
+}
+
+// Request handler for the overview of recurring expenses. Lists the existing recurring expenses and
+// the series of expenses that look like recurring expenses.
+pub async fn recurring_handler(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+    tera: web::Data<tera::Tera>,
+) -> Result<HttpResponse, Error> {
+    assert_authenticated(&id)?;
+
+    let user = current_user(&pool, &id).await?;
+    let recurring_expenses = db::recurring::get_recurring_expenses_async(&pool, &user)
+        .await
+        .map_err(error::ErrorInternalServerError)?;
+    let suggestions = db::recurring::detect_async(&pool, &user)
+        .await
+        .map_err(error::ErrorInternalServerError)?;
+
+    let mut context = get_tera_context("Recurring expenses", id);
+    context.insert("recurring_expenses", &recurring_expenses);
+    context.insert("suggestions", &suggestions);
+
+    let content = tera
+        .render("recurring.html", &context)
+        .map_err(|err| error::ErrorInternalServerError(format!("Template error: {:?}", err)))?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(content))
+}
+
+// Request handler for accepting a suggested recurring expense.
+pub async fn accept_submit(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+    input: web::Form<AcceptForm>,
+) -> Result<HttpResponse, Error> {
+    assert_authenticated(&id)?;
+
+    let user = current_user(&pool, &id).await?;
+    match db::recurring::accept_async(&pool, &user, input.category_id, &input.description).await {
+        Ok(_) => Ok(HttpResponse::SeeOther()
+            .header("location", "/recurring")
+            .finish()),
+        Err(AsyncError::Query(RecurringErrorKind::NoSuggestion(_, _))) => Err(
+            error::ErrorNotFound("No recurring expense was detected for these expenses."),
+        ),
+        Err(e) => Err(error::ErrorInternalServerError(e)),
+    }
+}
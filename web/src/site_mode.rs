@@ -0,0 +1,124 @@
+use actix_service::{Service, Transform};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::{Error, HttpResponse};
+use app::SiteMode;
+use futures::future::{ok, Either, Ready};
+use std::task::{Context, Poll};
+
+// Paths that accept form submissions in read-only mode because they do not modify any data.
+static READ_ONLY_ALLOWED_PATHS: [&str; 1] = ["/user/login"];
+
+/// Middleware that rejects requests depending on the mode the application is running in. In
+/// maintenance mode all requests are rejected, in read-only mode only requests that might modify
+/// data are rejected. Rejected requests get a 503 Service Unavailable response.
+pub struct SiteModeGuard {
+    mode: SiteMode,
+}
+
+impl SiteModeGuard {
+    pub fn new(mode: SiteMode) -> SiteModeGuard {
+        SiteModeGuard { mode }
+    }
+}
+
+impl<S, B> Transform<S> for SiteModeGuard
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = SiteModeGuardMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(SiteModeGuardMiddleware {
+            service,
+            mode: self.mode,
+        })
+    }
+}
+
+pub struct SiteModeGuardMiddleware<S> {
+    service: S,
+    mode: SiteMode,
+}
+
+impl<S, B> Service for SiteModeGuardMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        match rejection_message(self.mode, &req) {
+            None => Either::Left(self.service.call(req)),
+            Some(message) => {
+                let response = HttpResponse::ServiceUnavailable().body(message);
+                Either::Right(ok(req.into_response(response.into_body())))
+            }
+        }
+    }
+}
+
+// Returns the message to show to the user if the request should be rejected in the given mode.
+fn rejection_message(mode: SiteMode, req: &ServiceRequest) -> Option<&'static str> {
+    match mode {
+        SiteMode::Online => None,
+        SiteMode::Maintenance => Some("Firetrack is currently undergoing maintenance"),
+        SiteMode::ReadOnly => {
+            if is_read_only_request(req.method(), req.path()) {
+                None
+            } else {
+                Some("Firetrack is currently in read-only mode, changes cannot be saved")
+            }
+        }
+    }
+}
+
+// Returns whether the request with the given method and path is safe to handle in read-only mode.
+fn is_read_only_request(method: &Method, path: &str) -> bool {
+    match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS => true,
+        Method::POST => READ_ONLY_ALLOWED_PATHS.contains(&path),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tests is_read_only_request().
+    #[test]
+    fn test_is_read_only_request() {
+        let test_cases = [
+            (Method::GET, "/", true),
+            (Method::HEAD, "/", true),
+            (Method::OPTIONS, "/", true),
+            (Method::GET, "/user/register", true),
+            (Method::POST, "/user/register", false),
+            (Method::POST, "/user/activate", false),
+            (Method::POST, "/user/login", true),
+            (Method::PUT, "/user/login", false),
+            (Method::DELETE, "/", false),
+        ];
+
+        for (method, path, expected) in &test_cases {
+            assert_eq!(*expected, is_read_only_request(method, path));
+        }
+    }
+}
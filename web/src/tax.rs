@@ -0,0 +1,32 @@
+use crate::user::{assert_authenticated, current_user};
+use actix_identity::Identity;
+use actix_web::{error, web, Error, HttpResponse};
+use db::tax::TaxErrorKind;
+use db::AsyncError;
+
+// Request handler that downloads the yearly tax report of the user as a CSV file.
+pub async fn report_handler(
+    id: Identity,
+    pool: web::Data<db::ConnectionPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    assert_authenticated(&id)?;
+
+    let user = current_user(&pool, &id).await?;
+    let year = path.into_inner();
+    let report = match db::tax::get_yearly_report_async(&pool, &user, year).await {
+        Ok(report) => report,
+        Err(AsyncError::Query(TaxErrorKind::InvalidYear(_))) => {
+            return Err(error::ErrorBadRequest("The year is not supported."));
+        }
+        Err(e) => return Err(error::ErrorInternalServerError(e)),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .header(
+            "content-disposition",
+            format!("attachment; filename=\"taxes-{}.csv\"", year),
+        )
+        .body(report.to_csv()))
+}
@@ -6,7 +6,7 @@ use actix_web::{error, web, Error, HttpResponse};
 use app::AppConfig;
 use db::activation_code::ActivationCodeErrorKind;
 use db::user::UserErrorKind;
-use diesel::PgConnection;
+use db::AsyncError;
 use validator::validate_email;
 
 // The form fields of the user form.
@@ -67,8 +67,8 @@ impl UserFormValidation {
     }
 
     // Validates the user form when logging in.
-    pub fn validate_login(
-        connection: &PgConnection,
+    pub async fn validate_login(
+        pool: &db::ConnectionPool,
         config: &AppConfig,
         input: &UserForm,
     ) -> UserFormValidation {
@@ -76,7 +76,9 @@ impl UserFormValidation {
 
         if input.email.is_empty()
             || input.password.is_empty()
-            || db::user::verify_password(connection, &input.email, &input.password, config).is_err()
+            || db::user::verify_password_async(pool, &input.email, &input.password, config)
+                .await
+                .is_err()
         {
             // To prevent enumeration attacks we treat a non-existing email as a wrong password.
             validation_state.password = false;
@@ -116,10 +118,8 @@ pub async fn login_submit(
 ) -> Result<HttpResponse, Error> {
     assert_not_authenticated(&id)?;
 
-    let connection = pool.get().map_err(error::ErrorInternalServerError)?;
-
     // Validate the form input.
-    let validation_state = UserFormValidation::validate_login(&connection, &config, &input);
+    let validation_state = UserFormValidation::validate_login(&pool, &config, &input).await;
 
     // If validation failed, show the form again with validation errors highlighted.
     if !validation_state.is_valid() {
@@ -221,14 +221,14 @@ pub async fn register_submit(
     }
 
     // Create the user account.
-    let connection = pool.get().map_err(error::ErrorInternalServerError)?;
-    let result = db::user::create(&connection, &input.email, &input.password, &config);
+    let result = db::user::create_async(&pool, &input.email, &input.password, &config).await;
 
     // Check if a user account already exists with the given email address. The user might have
     // forgotten that they already have an account, or they might have intended to log in instead of
     // register.
-    if let Err(UserErrorKind::UserWithEmailAlreadyExists(_)) = result {
-        return if db::user::verify_password(&connection, &input.email, &input.password, &config)
+    if let Err(AsyncError::Query(UserErrorKind::UserWithEmailAlreadyExists(_))) = result {
+        return if db::user::verify_password_async(&pool, &input.email, &input.password, &config)
+            .await
             .is_ok()
         {
             // If the supplied credentials are correct, just transparently log in the user.
@@ -243,8 +243,9 @@ pub async fn register_submit(
     let user = result.map_err(error::ErrorInternalServerError)?;
 
     // Send an activation email.
-    let activation_code =
-        db::activation_code::get(&connection, &user).map_err(error::ErrorInternalServerError)?;
+    let activation_code = db::activation_code::get_async(&pool, &user)
+        .await
+        .map_err(error::ErrorInternalServerError)?;
     notifications::activate(&user, &activation_code, &config)
         .await
         .map_err(error::ErrorInternalServerError)?;
@@ -334,8 +335,7 @@ pub async fn activate_handler(
     // The email address is passed in the session by the registration / login form. Return an error
     // if it is not set or does not correspond with an existing, non-activated user.
     if let Some(email) = session.get::<String>("email").unwrap_or_else(|_| None) {
-        let connection = pool.get().map_err(error::ErrorInternalServerError)?;
-        if let Ok(user) = db::user::read(&connection, email.as_str()) {
+        if let Ok(user) = db::user::read_async(&pool, email.as_str()).await {
             if !user.activated {
                 let input = ActivationFormInput::new("".to_string());
                 let validation_state = ActivationFormInputValid::default();
@@ -391,21 +391,20 @@ pub async fn activate_submit(
 
     // Load the user from the email that is stored in the session.
     if let Some(email) = session.get::<String>("email").unwrap_or_else(|_| None) {
-        let connection = pool.get().map_err(error::ErrorInternalServerError)?;
-        if let Ok(user) = db::user::read(&connection, email.as_str()) {
-            match db::activation_code::activate_user(&connection, user, activation_code) {
-                Err(ActivationCodeErrorKind::Expired) => {
+        if let Ok(user) = db::user::read_async(&pool, email.as_str()).await {
+            match db::activation_code::activate_user_async(&pool, user, activation_code).await {
+                Err(AsyncError::Query(ActivationCodeErrorKind::Expired)) => {
                     return validation_error("The expiration code has expired. Please re-send the activation email and try again.");
                 }
-                Err(ActivationCodeErrorKind::UserAlreadyActivated(_)) => {
+                Err(AsyncError::Query(ActivationCodeErrorKind::UserAlreadyActivated(_))) => {
                     // In order to not disclose which email addresses are registered we treat this
                     // the same as a non-existing user trying to access the form.
                     return authorization_failed();
                 }
-                Err(ActivationCodeErrorKind::MaxAttemptsExceeded) => {
+                Err(AsyncError::Query(ActivationCodeErrorKind::MaxAttemptsExceeded)) => {
                     return validation_error("You have exceeded the maximum number of activation attempts. Please try again later.");
                 }
-                Err(ActivationCodeErrorKind::InvalidCode) => {
+                Err(AsyncError::Query(ActivationCodeErrorKind::InvalidCode)) => {
                     return validation_error("Incorrect activation code. Please try again.");
                 }
                 Err(e) => {
@@ -457,7 +456,7 @@ fn assert_not_authenticated(id: &Identity) -> Result<(), Error> {
 }
 
 // Checks that the user is authenticated.
-fn assert_authenticated(id: &Identity) -> Result<(), Error> {
+pub fn assert_authenticated(id: &Identity) -> Result<(), Error> {
     if id.identity().is_none() {
         return Err(error::ErrorForbidden(
             "You need to be logged in to access this page.",
@@ -466,6 +465,17 @@ fn assert_authenticated(id: &Identity) -> Result<(), Error> {
     Ok(())
 }
 
+// Returns the user that is logged in.
+pub async fn current_user(
+    pool: &db::ConnectionPool,
+    id: &Identity,
+) -> Result<db::user::User, Error> {
+    let email = id.identity().unwrap_or_default();
+    db::user::read_async(pool, &email)
+        .await
+        .map_err(error::ErrorInternalServerError)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;